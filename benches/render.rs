@@ -0,0 +1,76 @@
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use simple_html_template::Template;
+use std::collections::HashMap;
+
+// Builds a template with `n_placeholders` placeholders cycling through
+// `n_keys` distinct keys, and a context that fills all of them
+fn fixture(n_keys: usize, n_placeholders: usize) -> (String, Vec<String>, Vec<String>) {
+    let keys: Vec<String> = (0..n_keys).map(|i| format!("key_{}", i)).collect();
+    let values: Vec<String> = (0..n_keys).map(|i| format!("value number {}", i)).collect();
+
+    let mut template = String::new();
+    for i in 0..n_placeholders {
+        template.push_str("<li class=\"item\">");
+        template.push_str("${");
+        template.push_str(&keys[i % n_keys]);
+        template.push_str("}</li>\n");
+    }
+
+    (template, keys, values)
+}
+
+// The previous algorithm: every context entry is checked against every
+// placeholder, and every placeholder does its own HashMap lookup
+fn render_naive(template: &Template, vars: &HashMap<&str, &str>) -> Option<String> {
+    let placeholders: Vec<_> = template.placeholders().collect();
+
+    for k in vars.keys() {
+        if !placeholders.iter().any(|p| p.key == *k) {
+            return None;
+        }
+    }
+
+    let template_str = template.render_plain();
+    let mut output = String::with_capacity(template_str.len());
+    let mut cursor = 0;
+
+    for p in placeholders.iter() {
+        output.push_str(&template_str[cursor..p.start]);
+        output.push_str(vars.get(p.key)?);
+        cursor = p.end;
+    }
+    output.push_str(&template_str[cursor..]);
+
+    Some(output)
+}
+
+fn bench_render(c: &mut Criterion) {
+    let mut group = c.benchmark_group("render");
+
+    for (n_keys, n_placeholders) in [(10, 100), (100, 500), (300, 1000)].iter() {
+        let (source, keys, values) = fixture(*n_keys, *n_placeholders);
+        let template = Template::new(&source).unwrap();
+        let vars: HashMap<&str, &str> = keys
+            .iter()
+            .map(|k| k.as_str())
+            .zip(values.iter().map(|v| v.as_str()))
+            .collect();
+
+        assert_eq!(render_naive(&template, &vars).unwrap(), template.render(&vars).unwrap());
+
+        let id = format!("{}keys/{}placeholders", n_keys, n_placeholders);
+
+        group.bench_with_input(BenchmarkId::new("naive", &id), &vars, |b, vars| {
+            b.iter(|| render_naive(black_box(&template), black_box(vars)))
+        });
+
+        group.bench_with_input(BenchmarkId::new("slots", &id), &vars, |b, vars| {
+            b.iter(|| template.render(black_box(vars)))
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_render);
+criterion_main!(benches);
@@ -0,0 +1,87 @@
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use simple_html_template::Template;
+use std::collections::HashMap;
+
+// Builds an html document of roughly `n_rows` table rows, with inline CSS and
+// JS so there are plenty of braces that aren't placeholders.
+//
+// The CSS and JS come after the last placeholder, because the old scanner
+// pairs a `${` with any earlier `}` and would produce garbage otherwise.
+fn document(n_rows: usize) -> String {
+    let mut doc = String::from("<html><body><h1>${title}</h1><table>\n");
+
+    for i in 0..n_rows {
+        doc.push_str("<tr><td class=\"name\">${name_");
+        doc.push_str(&i.to_string());
+        doc.push_str("}</td><td>Lorem ipsum dolor sit amet, consectetur adipiscing elit, sed do eiusmod tempor</td>");
+        doc.push_str("<td>${price_");
+        doc.push_str(&i.to_string());
+        doc.push_str("}</td></tr>\n");
+    }
+
+    doc.push_str(
+        "</table><style>\n\
+         body { margin: 0; font-family: sans-serif; }\n\
+         table td { padding: 4px; border: 1px solid #ccc; }\n\
+         </style><script>\n\
+         document.querySelectorAll('td').forEach(td => { td.onclick = () => { console.log(td); }; });\n\
+         </script></body></html>",
+    );
+
+    doc
+}
+
+// (slot, (key_start, key_end))
+type Replace = (usize, (usize, usize));
+
+// The previous scanner: `find("${")` and then a separate `find('}')` from the
+// same cursor on every iteration. The keys go into the same kind of table that
+// `Template::new` builds, so the comparison is about scanning.
+fn scan_old(template_str: &str) -> (Vec<&str>, Vec<Replace>) {
+    let mut keys = Vec::new();
+    let mut slots = HashMap::new();
+    let mut replaces = Vec::new();
+    let mut cursor = 0;
+
+    while cursor < template_str.len() {
+        if let Some(start) = template_str[cursor..].find("${") {
+            let start = start + cursor;
+            let end = template_str[cursor..].find('}').unwrap() + cursor;
+            let key = &template_str[(start + "${".len())..end];
+            let slot = *slots.entry(key).or_insert_with(|| {
+                keys.push(key);
+                keys.len() - 1
+            });
+            replaces.push((slot, (start, end + "}".len())));
+            cursor = end + "}".len();
+        } else {
+            break;
+        }
+    }
+
+    (keys, replaces)
+}
+
+fn bench_scan(c: &mut Criterion) {
+    let mut group = c.benchmark_group("scan");
+
+    for n_rows in [100, 1_000, 10_000].iter() {
+        let doc = document(*n_rows);
+        assert_eq!(scan_old(&doc).0, Template::new(&doc).unwrap().keys());
+
+        group.throughput(Throughput::Bytes(doc.len() as u64));
+
+        group.bench_with_input(BenchmarkId::new("find", n_rows), &doc, |b, doc| {
+            b.iter(|| scan_old(black_box(doc)))
+        });
+
+        group.bench_with_input(BenchmarkId::new("memchr", n_rows), &doc, |b, doc| {
+            b.iter(|| Template::new(black_box(doc)).unwrap())
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_scan);
+criterion_main!(benches);
@@ -0,0 +1,68 @@
+use std::borrow::Cow;
+
+/// Builds up the text of a template out of pieces of the input
+///
+/// As long as every piece directly follows the previous one in the input, the
+/// result is just a slice of it. Anything else switches over to an owned
+/// string.
+pub(crate) struct Assembler<'a> {
+    input: &'a str,
+    // (start, end) of the slice so far, while nothing has been copied
+    borrowed: Option<(usize, usize)>,
+    owned: Option<String>,
+}
+
+impl<'a> Assembler<'a> {
+    pub fn new(input: &'a str) -> Self {
+        Self {
+            input,
+            borrowed: None,
+            owned: None,
+        }
+    }
+
+    /// Current length of the output, for recording spans
+    pub fn len(&self) -> usize {
+        match (&self.owned, self.borrowed) {
+            (Some(s), _) => s.len(),
+            (None, Some((start, end))) => end - start,
+            (None, None) => 0,
+        }
+    }
+
+    /// Pushes `input[start..end]`
+    pub fn push(&mut self, start: usize, end: usize) {
+        if start == end {
+            return;
+        }
+
+        match (&mut self.owned, &mut self.borrowed) {
+            (None, None) => self.borrowed = Some((start, end)),
+            (None, Some((_, prev_end))) if *prev_end == start => *prev_end = end,
+            _ => self.push_str(&self.input[start..end]),
+        }
+    }
+
+    /// Pushes text that doesn't come from the input
+    pub fn push_str(&mut self, s: &str) {
+        if s.is_empty() {
+            return;
+        }
+
+        let input = self.input;
+        let borrowed = self.borrowed;
+        let owned = self.owned.get_or_insert_with(|| match borrowed {
+            Some((start, end)) => input[start..end].to_string(),
+            None => String::new(),
+        });
+        owned.push_str(s);
+    }
+
+    pub fn finish(self) -> Cow<'a, str> {
+        match (self.owned, self.borrowed) {
+            (Some(s), _) => Cow::Owned(s),
+            (None, Some((start, end))) => Cow::Borrowed(&self.input[start..end]),
+            (None, None) => Cow::Borrowed(""),
+        }
+    }
+}
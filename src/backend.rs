@@ -0,0 +1,71 @@
+/// The kinds of node that [`DomBackend`] tells apart
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum NodeKind {
+    Element,
+    Text,
+    Comment,
+    /// A document fragment, e.g. the content of a `<template>`
+    Fragment,
+    /// Anything else, e.g. a doctype
+    Other,
+}
+
+/// The DOM operations that [`DomRenderer`](crate::DomRenderer) needs
+///
+/// There's `WebDom` for the browser with the `wasm` feature,
+/// and [`MemoryDom`](crate::MemoryDom) for rendering outside of a browser,
+/// e.g. in tests.
+///
+/// Functions that only make sense for some kinds of node (e.g.
+/// [`Self::set_attribute`] for elements) may panic when given a different
+/// kind.
+pub trait DomBackend {
+    type Node: Clone;
+
+    /// What gets attached to `on:event` attributes, see
+    /// [`Handlers`](crate::Handlers)
+    type Handler;
+
+    /// Creates a `<template>`, sets its inner HTML to `html`, and returns the
+    /// template's content
+    fn parse_fragment(&self, html:&str) -> Self::Node;
+
+    /// Deep copy of `node`, which belongs to the same document as the nodes
+    /// from [`Self::parse_fragment`]
+    fn clone_node(&self, node:&Self::Node) -> Self::Node;
+
+    fn kind(&self, node:&Self::Node) -> NodeKind;
+
+    fn create_text(&self, text:&str) -> Self::Node;
+
+    fn child_nodes(&self, node:&Self::Node) -> Vec<Self::Node>;
+
+    fn child(&self, node:&Self::Node, index:usize) -> Option<Self::Node> {
+        self.child_nodes(node).into_iter().nth(index)
+    }
+
+    /// Moves `node` into `parent`, before `reference`, or at the end if
+    /// there's no `reference`
+    fn insert_before(&self, parent:&Self::Node, node:&Self::Node, reference:Option<&Self::Node>);
+
+    /// Takes `node` out of its parent, if it has one
+    fn remove(&self, node:&Self::Node);
+
+    /// (name, value) of each attribute of an element
+    fn attributes(&self, node:&Self::Node) -> Vec<(String, String)>;
+
+    fn get_attribute(&self, node:&Self::Node, name:&str) -> Option<String>;
+
+    fn set_attribute(&self, node:&Self::Node, name:&str, value:&str);
+
+    fn remove_attribute(&self, node:&Self::Node, name:&str);
+
+    /// The text of a text or comment node
+    fn text(&self, node:&Self::Node) -> String;
+
+    fn set_text(&self, node:&Self::Node, text:&str);
+
+    fn add_listener(&self, node:&Self::Node, event:&str, handler:&Self::Handler);
+
+    fn remove_listener(&self, node:&Self::Node, event:&str, handler:&Self::Handler);
+}
@@ -0,0 +1,153 @@
+use crate::{evaluate, options, ContextValue, Errors, ExtraPolicy, RenderOptions, Template};
+use std::collections::{HashMap, HashSet};
+
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+
+/// Rendering lots of contexts against one template, e.g. table rows or
+/// emails
+///
+/// These all reuse their lookup buffers from one context to the next, instead
+/// of allocating for each one like calling [`Template::render`] in a loop
+/// would. The keys are only checked once per distinct shape of context, i.e.
+/// which of the template's keys it has and how many entries it has, so
+/// contexts that all have the same shape only pay for the key lookups. With
+/// [`ExtraPolicy::Warn`], every context is checked, so that each one's extra
+/// keys are passed to the function.
+impl <'a> Template <'a> {
+    /// Renders each context in turn, lazily
+    pub fn render_many<'v, V, I>(&'v self, contexts:I) -> impl Iterator<Item = Result<String, Errors>> + 'v
+    where
+        V: ContextValue + 'v,
+        I: IntoIterator<Item = &'v HashMap<&'v str, V>>,
+        I::IntoIter: 'v,
+    {
+        self.render_many_with(contexts, &options::STRICT)
+    }
+
+    pub fn render_many_with<'v, V, I>(&'v self, contexts:I, options:&'v RenderOptions) -> impl Iterator<Item = Result<String, Errors>> + 'v
+    where
+        V: ContextValue + 'v,
+        I: IntoIterator<Item = &'v HashMap<&'v str, V>>,
+        I::IntoIter: 'v,
+    {
+        let mut shapes = Shapes::new();
+        let mut found = Vec::with_capacity(self.keys.len());
+        let mut values = Vec::with_capacity(self.keys.len());
+
+        contexts.into_iter().map(move |vars| {
+            self.find(vars, &mut found);
+            shapes.check(self, vars, &found, options)?;
+            evaluate(&found, &mut values);
+
+            let mut output = String::with_capacity(self.output_len(&values, options));
+            self.write(&values, options, &mut output);
+            Ok(output)
+        })
+    }
+
+    /// Renders each context and joins the results with `separator`, all into
+    /// one buffer
+    ///
+    /// Stops at the first context that fails.
+    pub fn render_join<'v, V, I>(&self, contexts:I, separator:&str) -> Result<String, Errors>
+    where
+        V: ContextValue + 'v,
+        I: IntoIterator<Item = &'v HashMap<&'v str, V>>,
+    {
+        self.render_join_with(contexts, separator, &options::STRICT)
+    }
+
+    pub fn render_join_with<'v, V, I>(&self, contexts:I, separator:&str, options:&RenderOptions) -> Result<String, Errors>
+    where
+        V: ContextValue + 'v,
+        I: IntoIterator<Item = &'v HashMap<&'v str, V>>,
+    {
+        let mut shapes = Shapes::new();
+        let mut found = Vec::with_capacity(self.keys.len());
+        let mut values = Vec::with_capacity(self.keys.len());
+        let mut output = String::new();
+
+        for (i, vars) in contexts.into_iter().enumerate() {
+            self.find(vars, &mut found);
+            shapes.check(self, vars, &found, options)?;
+            evaluate(&found, &mut values);
+
+            if i > 0 {
+                output.push_str(separator);
+            }
+            output.reserve(self.output_len(&values, options));
+            self.write(&values, options, &mut output);
+        }
+
+        Ok(output)
+    }
+
+    /// Same as [`Self::render_many`] but spread over the rayon thread pool,
+    /// with the results in the same order as `contexts`
+    ///
+    /// The values have to be `Sync`, which rules out [`Value`](crate::Value),
+    /// since its lazy closures don't have to be. Compute them up front into
+    /// `String`s instead.
+    #[cfg(feature = "parallel")]
+    pub fn par_render_many<V: ContextValue + Sync>(&self, contexts:&[HashMap<&str, V>]) -> Vec<Result<String, Errors>> {
+        self.par_render_many_with(contexts, &options::STRICT)
+    }
+
+    #[cfg(feature = "parallel")]
+    pub fn par_render_many_with<V: ContextValue + Sync>(&self, contexts:&[HashMap<&str, V>], options:&RenderOptions) -> Vec<Result<String, Errors>> {
+        contexts
+            .par_iter()
+            .map_init(
+                || (Shapes::new(), Vec::with_capacity(self.keys.len()), Vec::with_capacity(self.keys.len())),
+                |(shapes, found, values), vars| {
+                    self.find(vars, found);
+                    shapes.check(self, vars, found, options)?;
+                    evaluate(found, values);
+
+                    let mut output = String::with_capacity(self.output_len(values, options));
+                    self.write(values, options, &mut output);
+                    Ok(output)
+                },
+            )
+            .collect()
+    }
+}
+
+// The shapes of the contexts that have passed `Template::check` so far
+//
+// A shape is which of the template's keys a context has, and how many entries
+// it has in total. Contexts with the same shape are missing the same keys, and
+// have the same number of extra keys, so once one of them passes, so does
+// every other one: either there are no extras, or they're ignored.
+struct Shapes {
+    seen: HashSet<(Vec<bool>, usize)>,
+    // Reused for each context
+    current: (Vec<bool>, usize),
+}
+
+impl Shapes {
+    fn new() -> Self {
+        Self {
+            seen: HashSet::new(),
+            current: (Vec::new(), 0),
+        }
+    }
+
+    fn check<V>(&mut self, template:&Template, vars:&HashMap<&str, V>, found:&[Option<&V>], options:&RenderOptions) -> Result<(), Errors> {
+        // The extra keys' names matter here
+        if let ExtraPolicy::Warn(_) = options.extra {
+            return template.check(vars, found, options);
+        }
+
+        self.current.0.clear();
+        self.current.0.extend(found.iter().map(Option::is_some));
+        self.current.1 = vars.len();
+
+        if !self.seen.contains(&self.current) {
+            template.check(vars, found, options)?;
+            self.seen.insert(self.current.clone());
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,221 @@
+//! `sht`, renders a template file from the command line
+//!
+//! Built with the `cli` feature: `cargo install simple-html-template --features cli`
+
+use simple_html_template::{RenderOptions, Template, Whitespace};
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::process;
+
+const USAGE: &str = "\
+Usage: sht [OPTIONS] <TEMPLATE>
+
+Renders TEMPLATE (or stdin, if it's -) to stdout. Values are put in as they
+are, without escaping. When the same key comes from more than one place, the
+last one wins.
+
+Options:
+  -j, --json <FILE>      Values from a JSON object
+  -t, --toml <FILE>      Values from a TOML table
+  -e, --env <PREFIX>     Values from environment variables starting with
+                         PREFIX, keyed by the rest of the name
+  -s, --set <KEY=VALUE>  A single value
+  -o, --output <FILE>    Write to FILE instead of stdout
+  -w, --whitespace <WS>  What to do with whitespace in the template: trim
+                         the start and end (default), preserve, or collapse
+      --strict           Fail on values the template doesn't use (default)
+      --lenient          Ignore values the template doesn't use
+  -h, --help             Show this message
+
+Nested tables are flattened, so {\"site\": {\"title\": ...}} fills ${site.title}.
+Numbers and booleans are written as they'd appear in the file, and null is
+empty.";
+
+// Exit codes
+const FAILED: i32 = 1;
+const BAD_USAGE: i32 = 2;
+
+struct Args {
+    template: String,
+    output: Option<String>,
+    whitespace: Whitespace,
+    options: RenderOptions,
+    // Applied in order
+    sources: Vec<Source>,
+}
+
+enum Source {
+    Json(String),
+    Toml(String),
+    Env(String),
+    Set(String, String),
+}
+
+fn main() {
+    let args = match parse_args(env::args().skip(1)) {
+        Ok(Some(args)) => args,
+        Ok(None) => {
+            println!("{}", USAGE);
+            return;
+        },
+        Err(e) => {
+            eprintln!("sht: {}\n\n{}", e, USAGE);
+            process::exit(BAD_USAGE);
+        },
+    };
+
+    if let Err(e) = run(args) {
+        eprintln!("sht: {}", e);
+        process::exit(FAILED);
+    }
+}
+
+fn run(args:Args) -> Result<(), String> {
+    let Args { template: path, output, whitespace, options, sources } = args;
+
+    let text = match path.as_str() {
+        "-" => {
+            let mut s = String::new();
+            io::stdin().read_to_string(&mut s).map_err(|e| format!("can't read stdin: {}", e))?;
+            s
+        },
+        path => read(path)?,
+    };
+
+    let mut context = HashMap::new();
+    for source in sources {
+        match source {
+            Source::Json(path) => {
+                let value:serde_json::Value = serde_json::from_str(&read(&path)?).map_err(|e| format!("{}: {}", path, e))?;
+                match value {
+                    serde_json::Value::Object(map) => flatten_json("", map, &mut context).map_err(|e| format!("{}: {}", path, e))?,
+                    _ => return Err(format!("{}: expected an object", path)),
+                }
+            },
+            Source::Toml(path) => {
+                let table:toml::Table = read(&path)?.parse().map_err(|e| format!("{}: {}", path, e))?;
+                flatten_toml("", table, &mut context).map_err(|e| format!("{}: {}", path, e))?;
+            },
+            Source::Env(prefix) => {
+                for (name, value) in env::vars() {
+                    if let Some(key) = name.strip_prefix(&prefix) {
+                        if !key.is_empty() {
+                            context.insert(key.to_string(), value);
+                        }
+                    }
+                }
+            },
+            Source::Set(key, value) => {
+                context.insert(key, value);
+            },
+        }
+    }
+
+    let template = Template::builder(&text).whitespace(whitespace).build().map_err(|e| format!("{}: {}", path, e))?;
+    let vars:HashMap<&str, &str> = context.iter().map(|(k, v)| (k.as_str(), v.as_str())).collect();
+    let rendered = template.render_with(&vars, &options).map_err(|e| format!("{}: {}", path, e))?;
+
+    match output {
+        Some(out) => fs::write(&out, rendered).map_err(|e| format!("can't write {}: {}", out, e)),
+        None => io::stdout().write_all(rendered.as_bytes()).map_err(|e| format!("can't write to stdout: {}", e)),
+    }
+}
+
+// Ok(None) means --help
+fn parse_args(mut args:impl Iterator<Item = String>) -> Result<Option<Args>, String> {
+    let mut template = None;
+    let mut output = None;
+    let mut whitespace = Whitespace::default();
+    let mut options = RenderOptions::strict();
+    let mut sources = Vec::new();
+
+    while let Some(arg) = args.next() {
+        // --name=value is the same as --name value
+        let (name, inline) = match arg.find('=') {
+            Some(i) if arg.starts_with("--") => (arg[..i].to_string(), Some(arg[i + 1..].to_string())),
+            _ => (arg.clone(), None),
+        };
+        let mut value = || match inline.clone().or_else(|| args.next()) {
+            Some(value) => Ok(value),
+            None => Err(format!("{} needs a value", name)),
+        };
+
+        match name.as_str() {
+            "-h" | "--help" => return Ok(None),
+            "-j" | "--json" => sources.push(Source::Json(value()?)),
+            "-t" | "--toml" => sources.push(Source::Toml(value()?)),
+            "-e" | "--env" => sources.push(Source::Env(value()?)),
+            "-s" | "--set" => {
+                let pair = value()?;
+                match pair.find('=') {
+                    Some(i) => sources.push(Source::Set(pair[..i].to_string(), pair[i + 1..].to_string())),
+                    None => return Err(format!("expected KEY=VALUE, got \"{}\"", pair)),
+                }
+            },
+            "-o" | "--output" => output = Some(value()?),
+            "-w" | "--whitespace" => {
+                whitespace = match value()?.as_str() {
+                    "trim" => Whitespace::Trim,
+                    "preserve" => Whitespace::Preserve,
+                    "collapse" => Whitespace::Collapse,
+                    other => return Err(format!("unknown whitespace setting \"{}\"", other)),
+                }
+            },
+            "--strict" => options = RenderOptions::strict(),
+            "--lenient" => options = RenderOptions::lenient(),
+            _ if name.starts_with('-') && name != "-" => return Err(format!("unknown option {}", name)),
+            _ => match template {
+                None => template = Some(arg),
+                Some(_) => return Err(format!("unexpected argument {}", arg)),
+            },
+        }
+    }
+
+    match template {
+        Some(template) => Ok(Some(Args { template, output, whitespace, options, sources })),
+        None => Err("no template given".to_string()),
+    }
+}
+
+fn read(path:&str) -> Result<String, String> {
+    fs::read_to_string(path).map_err(|e| format!("can't read {}: {}", path, e))
+}
+
+fn flatten_json(prefix:&str, map:serde_json::Map<String, serde_json::Value>, context:&mut HashMap<String, String>) -> Result<(), String> {
+    for (key, value) in map {
+        let key = format!("{}{}", prefix, key);
+        let value = match value {
+            serde_json::Value::String(s) => s,
+            serde_json::Value::Null => String::new(),
+            serde_json::Value::Bool(b) => b.to_string(),
+            serde_json::Value::Number(n) => n.to_string(),
+            serde_json::Value::Object(map) => {
+                flatten_json(&format!("{}.", key), map, context)?;
+                continue;
+            },
+            serde_json::Value::Array(_) => return Err(format!("\"{}\" is an array, which can't be a value", key)),
+        };
+        context.insert(key, value);
+    }
+    Ok(())
+}
+
+fn flatten_toml(prefix:&str, table:toml::Table, context:&mut HashMap<String, String>) -> Result<(), String> {
+    for (key, value) in table {
+        let key = format!("{}{}", prefix, key);
+        let value = match value {
+            toml::Value::String(s) => s,
+            toml::Value::Table(table) => {
+                flatten_toml(&format!("{}.", key), table, context)?;
+                continue;
+            },
+            toml::Value::Array(_) => return Err(format!("\"{}\" is an array, which can't be a value", key)),
+            // Integers, floats, booleans and datetimes
+            other => other.to_string(),
+        };
+        context.insert(key, value);
+    }
+    Ok(())
+}
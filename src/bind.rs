@@ -0,0 +1,824 @@
+//! Filling in a parsed template's DOM nodes, for any [`DomBackend`]
+
+use crate::backend::{DomBackend, NodeKind};
+use crate::builder::REF_ATTRIBUTE;
+use crate::{ContextValue, Error, Errors, Mark, MissingPolicy, RenderOptions, Template};
+use std::borrow::Cow;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::rc::Rc;
+
+// Placeholders are swapped for `MARK_START`, the slot number and `MARK_END`
+// in the HTML that's parsed, so that they can be found in the DOM afterwards.
+// These are in the private use area, which the HTML parser leaves alone.
+const MARK_START:char = '\u{E000}';
+const MARK_END:char = '\u{E001}';
+
+// `on:click="${key}"` adds the handler for `key` as a click listener
+const EVENT_PREFIX:&str = "on:";
+
+// Renders for hydration number the elements that the client needs to find
+// with this attribute, in document order
+pub(crate) const HYDRATE_ATTRIBUTE:&str = "data-sht";
+
+// In those renders, each row of a list comes right after a comment with this
+// text, so the client can tell the rows apart. The elements in a row are
+// numbered on their own, starting from 0.
+pub(crate) const ROW_MARKER:&str = "sht-row";
+
+// A `${#each ...}` becomes this attribute in the HTML that's parsed, with the
+// index of its mark in the template, so the names in it don't need escaping
+const EACH_ATTRIBUTE:&str = "data-sht-each";
+
+/// The elements in a rendered template that were marked with
+/// `data-ref="name"` or `${ref name}`, by name
+///
+/// If several elements have the same name, the first one is kept.
+#[derive(Clone, Debug)]
+pub struct Refs<N> {
+    elems: HashMap<String, N>,
+}
+
+impl <N> Refs<N> {
+    pub fn get(&self, name:&str) -> Option<&N> {
+        self.elems.get(name)
+    }
+
+    pub fn len(&self) -> usize {
+        self.elems.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.elems.is_empty()
+    }
+
+    pub fn into_inner(self) -> HashMap<String, N> {
+        self.elems
+    }
+
+    #[cfg(feature = "wasm")]
+    pub(crate) fn map<M>(self, f:impl Fn(N) -> M) -> Refs<M> {
+        Refs {
+            elems: self.elems.into_iter().map(|(name, node)| (name, f(node))).collect(),
+        }
+    }
+}
+
+impl <N> Default for Refs<N> {
+    fn default() -> Self {
+        Self { elems: HashMap::new() }
+    }
+}
+
+// A template parsed into DOM nodes
+pub(crate) struct Parsed<B: DomBackend> {
+    // Only the keys, see `Template::keys_only`
+    pub(crate) template: Rc<Template<'static>>,
+    // Which template this was parsed from, see `fingerprint`
+    fingerprint: u64,
+    // The first slot that can't be filled in directly, if any
+    pub(crate) bound: Result<Rc<Bound<B>>, usize>,
+}
+
+// Not derived, since that would need `B: Clone`
+impl <B: DomBackend> Clone for Parsed<B> {
+    fn clone(&self) -> Self {
+        Self {
+            template: self.template.clone(),
+            fingerprint: self.fingerprint,
+            bound: self.bound.clone(),
+        }
+    }
+}
+
+pub(crate) struct Bound<B: DomBackend> {
+    content: B::Node,
+    pub(crate) bindings: Vec<Binding>,
+    // The path to each element with a `data-ref`, which has already been
+    // removed from `content`
+    refs: Vec<(Vec<usize>, String)>,
+    // The `on:event` attributes, which have also been removed
+    pub(crate) events: Vec<EventBinding>,
+    // The elements for each `${#each ...}`, in the order of the marks
+    pub(crate) lists: Vec<ListBinding>,
+}
+
+impl <B: DomBackend> Parsed<B> {
+    pub(crate) fn new(dom:&B, template:&Template) -> Self {
+        let slots:Vec<String> = (0..template.keys.len()).map(|slot| format!("{}{}{}", MARK_START, slot, MARK_END)).collect();
+        let mut bound:Bound<B> = Bound {
+            content: dom.parse_fragment(&html(template, &slots)),
+            bindings: Vec::new(),
+            refs: Vec::new(),
+            events: Vec::new(),
+            lists: Vec::new(),
+        };
+        // The template's own text can't have the marks in it, which it could
+        // as entities like `&#xE000;`, so it's checked after parsing. Without
+        // any bindings, the slots come up missing below.
+        let empty = vec![""; template.keys.len()];
+        if !has_marks(dom, &dom.parse_fragment(&html(template, &empty))) {
+            let content = bound.content.clone();
+            find_bindings(dom, &content, &mut Vec::new(), &template.marks, &mut bound);
+        }
+
+        // Every placeholder has to have ended up in a text, comment or
+        // attribute value for the nodes to be filled in, and exactly once,
+        // since the parser can copy elements when fixing up bad nesting
+        let mut counts = vec![0i64; template.keys.len()];
+        for (slot, _) in template.replaces.iter() {
+            counts[*slot] += 1;
+        }
+        for slot in bound.bindings.iter().flat_map(|binding| binding.slots()) {
+            counts[slot] -= 1;
+        }
+        for event in bound.events.iter() {
+            counts[event.slot] -= 1;
+        }
+
+        Self {
+            template: Rc::new(template.keys_only()),
+            fingerprint: fingerprint(template),
+            bound: match counts.iter().position(|count| *count != 0) {
+                Some(slot) => Err(slot),
+                None => Ok(Rc::new(bound)),
+            },
+        }
+    }
+
+    // Whether this was parsed from `template`
+    pub(crate) fn is_for(&self, template:&Template) -> bool {
+        self.fingerprint == fingerprint(template)
+    }
+
+    // The bindings, or an error for templates that can only be rendered
+    // through HTML
+    pub(crate) fn bound(&self) -> Result<&Rc<Bound<B>>, Errors> {
+        self.bound.as_ref().map_err(|slot| Errors {
+            inner: vec![Error::Unbindable(self.template.keys[*slot].to_string())],
+        })
+    }
+
+    // The slots that are only used for event handlers, see `texts`
+    pub(crate) fn handler_only_slots(&self) -> Vec<usize> {
+        match &self.bound {
+            Ok(bound) => bound.handler_only_slots(),
+            Err(_) => Vec::new(),
+        }
+    }
+
+    // Makes a clone of the parsed template with `texts[slot]` set as the text
+    // of the node or attribute that each placeholder is in
+    pub(crate) fn fill(&self, dom:&B, texts:&[Cow<str>]) -> Result<(B::Node, Refs<B::Node>), Errors> {
+        let bound = self.bound()?;
+        let frag = bound.clone_content(dom);
+        for binding in bound.bindings.iter() {
+            binding.set(dom, &binding.node(dom, &frag), texts);
+        }
+        let refs = bound.refs(dom, &frag);
+        Ok((frag, refs))
+    }
+}
+
+// Tells templates apart without keeping a copy of them. The text isn't enough,
+// since e.g. `${x}` and `${raw}${x}${/raw}` both have `${x}` as their text.
+fn fingerprint(template:&Template) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    template.template_str.hash(&mut hasher);
+    template.keys.hash(&mut hasher);
+    template.replaces.hash(&mut hasher);
+    template.marks.hash(&mut hasher);
+    hasher.finish()
+}
+
+// The HTML for DOM renders to parse, which is `template` with `texts[slot]` in
+// place of each placeholder, as it is, and the marks put in
+pub(crate) fn html<S: AsRef<str>>(template:&Template, texts:&[S]) -> String {
+    let source = &template.template_str;
+    let mut html = String::with_capacity(source.len());
+    let mut marks = template.marks.iter().enumerate().peekable();
+
+    let mut cursor = 0;
+    for (slot, (start, end)) in template.replaces.iter() {
+        while let Some((i, (position, mark))) = marks.next_if(|(_, (position, _))| position <= start) {
+            html.push_str(&source[cursor..*position]);
+            push_mark(&mut html, i, mark);
+            cursor = *position;
+        }
+        html.push_str(&source[cursor..*start]);
+        html.push_str(texts[*slot].as_ref());
+        cursor = *end;
+    }
+    for (i, (position, mark)) in marks {
+        html.push_str(&source[cursor..*position]);
+        push_mark(&mut html, i, mark);
+        cursor = *position;
+    }
+    html.push_str(&source[cursor..]);
+
+    html
+}
+
+// With spaces around it, since the mark took the place of a tag that may not
+// have had any. `i` is the index of the mark in the template.
+fn push_mark(html:&mut String, i:usize, mark:&Mark) {
+    let (name, value) = match mark {
+        Mark::Ref(name) => (REF_ATTRIBUTE, name.to_string()),
+        Mark::Each { .. } => (EACH_ATTRIBUTE, i.to_string()),
+    };
+    html.push(' ');
+    html.push_str(name);
+    html.push_str("=\"");
+    html.push_str(&value);
+    html.push_str("\" ");
+}
+
+// The element for each `${#each ...}` in a parsed render, with the index of
+// its mark in the template
+pub(crate) type Lists<N> = Vec<(usize, N)>;
+
+// Renders `template` to HTML with `texts[slot]` in place of each placeholder,
+// as it is, and parses that. Also returns the elements for the rows of the
+// lists to go in.
+pub(crate) fn parse<B: DomBackend>(dom:&B, template:&Template, texts:&[Cow<str>]) -> (B::Node, Refs<B::Node>, Lists<B::Node>) {
+    let frag = dom.parse_fragment(&html(template, texts));
+    let refs = take_refs(dom, &frag);
+    let lists = take_lists(dom, &frag, template);
+    strip_events(dom, &frag);
+    (frag, refs, lists)
+}
+
+// The nodes in a rendered template that an instance works with
+pub(crate) struct Located<N> {
+    // The node for each binding
+    pub(crate) targets: Vec<N>,
+    // The element for each event binding
+    pub(crate) events: Vec<N>,
+    pub(crate) refs: Refs<N>,
+    // The element for each list
+    pub(crate) lists: Vec<N>,
+    // The top-level nodes
+    pub(crate) roots: Vec<N>,
+}
+
+// The nodes that a render for hydration became in the client's DOM: `nodes`
+// are children of `parent`, just before `next` (or at the end)
+pub(crate) struct Rendered<'n, N> {
+    pub(crate) parent: &'n N,
+    pub(crate) nodes: Vec<N>,
+    pub(crate) next: Option<&'n N>,
+}
+
+impl <B: DomBackend> Bound<B> {
+    // The referenced elements in a clone of the parsed template
+    pub(crate) fn refs(&self, dom:&B, root:&B::Node) -> Refs<B::Node> {
+        let mut refs = Refs::default();
+        for (path, name) in self.refs.iter() {
+            refs.elems.insert(name.clone(), follow(dom, root, path));
+        }
+        refs
+    }
+
+    pub(crate) fn locate(&self, dom:&B, root:&B::Node) -> Located<B::Node> {
+        Located {
+            targets: self.bindings.iter().map(|binding| binding.node(dom, root)).collect(),
+            events: self.events.iter().map(|event| event.node(dom, root)).collect(),
+            refs: self.refs(dom, root),
+            lists: self.lists.iter().map(|list| follow(dom, root, &list.path)).collect(),
+            roots: dom.child_nodes(root),
+        }
+    }
+
+    // The paths of the elements that hydration needs to find, in document
+    // order. Texts and comments are found from their parent elements, or the
+    // container at the top level.
+    fn anchors(&self) -> Vec<&[usize]> {
+        let mut anchors:Vec<&[usize]> = Vec::new();
+        for binding in self.bindings.iter() {
+            anchors.push(match binding.target {
+                Target::Data => &binding.path[..binding.path.len() - 1],
+                Target::Attribute(_) => &binding.path,
+            });
+        }
+        anchors.extend(self.events.iter().map(|event| event.path.as_slice()));
+        anchors.extend(self.refs.iter().map(|(path, _)| path.as_slice()));
+        anchors.extend(self.lists.iter().map(|list| list.path.as_slice()));
+
+        anchors.retain(|path| !path.is_empty());
+        anchors.sort_unstable();
+        anchors.dedup();
+        anchors
+    }
+
+    // Numbers the anchors in a clone of the parsed template
+    pub(crate) fn mark(&self, dom:&B, root:&B::Node) {
+        for (i, path) in self.anchors().into_iter().enumerate() {
+            dom.set_attribute(&follow(dom, root, path), HYDRATE_ATTRIBUTE, &i.to_string());
+        }
+    }
+
+    // Finds the nodes in DOM that was parsed from a render with `mark`
+    pub(crate) fn hydrate(&self, dom:&B, rendered:Rendered<B::Node>, keys:&[Cow<str>]) -> Result<Located<B::Node>, Error> {
+        let anchors = self.anchors();
+        let Rendered { parent: container, nodes: mut roots, next } = rendered;
+
+        let mut found = vec![None; anchors.len()];
+        take_marked(dom, &roots, &mut found);
+        let elems = found
+            .into_iter()
+            .enumerate()
+            .map(|(i, elem)| elem.ok_or_else(|| Error::Hydration(format!("no element with {}=\"{}\"", HYDRATE_ATTRIBUTE, i))))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let elem = |path:&[usize]| match anchors.binary_search(&path) {
+            Ok(i) => elems[i].clone(),
+            Err(_) => container.clone(),
+        };
+
+        let mut targets = Vec::with_capacity(self.bindings.len());
+        for binding in self.bindings.iter() {
+            let node = match binding.target {
+                Target::Attribute(_) => Some(elem(&binding.path)),
+                Target::Data => {
+                    let (index, path) = binding.path.split_last().unwrap();
+                    if path.is_empty() {
+                        self.hydrate_data(dom, container, &mut roots, next, path, *index)
+                    } else {
+                        let parent = elem(path);
+                        self.hydrate_data(dom, &parent, &mut dom.child_nodes(&parent), None, path, *index)
+                    }
+                },
+            };
+            let key = &keys[binding.slots().next().unwrap()];
+            targets.push(node.ok_or_else(|| Error::Hydration(format!("no node for key \"{}\"", key)))?);
+        }
+
+        let mut refs = Refs::default();
+        for (path, name) in self.refs.iter() {
+            refs.elems.insert(name.clone(), elem(path));
+        }
+
+        Ok(Located {
+            targets,
+            events: self.events.iter().map(|event| elem(&event.path)).collect(),
+            refs,
+            lists: self.lists.iter().map(|list| elem(&list.path)).collect(),
+            roots,
+        })
+    }
+
+    // Finds the text or comment that's child `index` of the node at `path` in
+    // the parsed template, among `children`, which are children of `parent`
+    // that come before `next`. A text that has to be added goes in
+    // `children` too.
+    //
+    // The text in the rendered DOM can't be counted on to line up, since
+    // empty text isn't there at all after parsing, so only the other nodes
+    // are counted. Between two of them there's at most one text node, since
+    // the parser merges them.
+    fn hydrate_data(&self, dom:&B, parent:&B::Node, children:&mut Vec<B::Node>, next:Option<&B::Node>, path:&[usize], index:usize) -> Option<B::Node> {
+        let is_text = |node:&B::Node| dom.kind(node) == NodeKind::Text;
+
+        let template_children = dom.child_nodes(&follow(dom, &self.content, path));
+        let before = template_children[..index].iter().filter(|node| !is_text(node)).count();
+
+        let others:Vec<usize> = (0..children.len()).filter(|i| !is_text(&children[*i])).collect();
+
+        if !is_text(&template_children[index]) {
+            let node = &children[*others.get(before)?];
+            return match dom.kind(node) {
+                NodeKind::Comment => Some(node.clone()),
+                _ => None,
+            };
+        }
+
+        let start = match before {
+            0 => 0,
+            n => others.get(n - 1)? + 1,
+        };
+        match children.get(start) {
+            Some(node) if is_text(node) => Some(node.clone()),
+            reference => {
+                let text = dom.create_text("");
+                dom.insert_before(parent, &text, reference.or(next));
+                children.insert(start, text.clone());
+                Some(text)
+            }
+        }
+    }
+
+    pub(crate) fn event_slots(&self) -> Vec<usize> {
+        let mut slots:Vec<usize> = self.events.iter().map(|event| event.slot).collect();
+        slots.sort_unstable();
+        slots.dedup();
+        slots
+    }
+
+    // The event slots that aren't also used for text somewhere
+    pub(crate) fn handler_only_slots(&self) -> Vec<usize> {
+        let mut slots = self.event_slots();
+        slots.retain(|slot| self.bindings.iter().all(|binding| binding.slots().all(|other| other != *slot)));
+        slots
+    }
+
+    pub(crate) fn clone_content(&self, dom:&B) -> B::Node {
+        dom.clone_node(&self.content)
+    }
+}
+
+// A text, comment or attribute value in the parsed template that has
+// placeholders in it
+pub(crate) struct Binding {
+    // Child indexes from the top of the fragment down to the node
+    path: Vec<usize>,
+    target: Target,
+    parts: Vec<Part>,
+}
+
+enum Target {
+    // The text of a text or comment node
+    Data,
+    // An attribute of an element
+    Attribute(String),
+}
+
+impl Binding {
+    pub(crate) fn slots(&self) -> impl Iterator<Item = usize> + '_ {
+        self.parts.iter().filter_map(|part| match part {
+            Part::Slot(slot) => Some(*slot),
+            Part::Text(_) => None,
+        })
+    }
+
+    // Finds the node in a clone of the parsed template
+    pub(crate) fn node<B: DomBackend>(&self, dom:&B, root:&B::Node) -> B::Node {
+        follow(dom, root, &self.path)
+    }
+
+    pub(crate) fn set<B: DomBackend, S: AsRef<str>>(&self, dom:&B, node:&B::Node, texts:&[S]) {
+        let value = join(&self.parts, texts);
+        match &self.target {
+            Target::Data => dom.set_text(node, &value),
+            Target::Attribute(name) => dom.set_attribute(node, name, &value),
+        }
+    }
+}
+
+// An `on:event="${key}"` attribute in the parsed template
+pub(crate) struct EventBinding {
+    path: Vec<usize>,
+    // e.g. `click`
+    pub(crate) event: String,
+    pub(crate) slot: usize,
+}
+
+impl EventBinding {
+    pub(crate) fn node<B: DomBackend>(&self, dom:&B, root:&B::Node) -> B::Node {
+        follow(dom, root, &self.path)
+    }
+}
+
+// The element for a `${#each ...}`, whose children are rendered from another
+// template
+pub(crate) struct ListBinding {
+    path: Vec<usize>,
+    // The key of the items
+    pub(crate) items: String,
+    // The name of the template for the rows
+    pub(crate) template: String,
+    // The field that the rows are keyed by, if any
+    pub(crate) key: Option<String>,
+}
+
+impl ListBinding {
+    pub(crate) fn node<B: DomBackend>(&self, dom:&B, root:&B::Node) -> B::Node {
+        follow(dom, root, &self.path)
+    }
+}
+
+// A piece of a text or attribute value that has placeholders in it
+enum Part {
+    Text(String),
+    Slot(usize),
+}
+
+fn parts(s:&str) -> Vec<Part> {
+    let mut parts = Vec::new();
+    let mut rest = s;
+
+    while let Some(start) = rest.find(MARK_START) {
+        if start > 0 {
+            parts.push(Part::Text(rest[..start].to_string()));
+        }
+        let after = &rest[start + MARK_START.len_utf8()..];
+        let end = after.find(MARK_END).unwrap();
+        parts.push(Part::Slot(after[..end].parse().unwrap()));
+        rest = &after[end + MARK_END.len_utf8()..];
+    }
+
+    if !rest.is_empty() {
+        parts.push(Part::Text(rest.to_string()));
+    }
+
+    parts
+}
+
+fn join<S: AsRef<str>>(parts:&[Part], texts:&[S]) -> String {
+    let mut output = String::new();
+    for part in parts {
+        match part {
+            Part::Text(text) => output.push_str(text),
+            Part::Slot(slot) => output.push_str(texts[*slot].as_ref()),
+        }
+    }
+    output
+}
+
+fn follow<B: DomBackend>(dom:&B, root:&B::Node, path:&[usize]) -> B::Node {
+    path.iter().fold(root.clone(), |node, i| dom.child(&node, *i).unwrap())
+}
+
+// Records a binding for every text, comment and attribute value under `node`
+// that has a placeholder in it, and takes the `data-ref`s, lists and event
+// handlers out
+fn find_bindings<B: DomBackend>(dom:&B, node:&B::Node, path:&mut Vec<usize>, marks:&[(usize, Mark)], bound:&mut Bound<B>) {
+    match dom.kind(node) {
+        NodeKind::Text | NodeKind::Comment => {
+            let value = dom.text(node);
+            if value.contains(MARK_START) {
+                let parts = parts(&value);
+
+                // Something like `<${tag}>` is parsed as text, but it isn't
+                // meant to be, so it's left unbound
+                let tag_like = parts.windows(2).any(|pair| match pair {
+                    [Part::Text(text), Part::Slot(_)] => text.ends_with('<') || text.ends_with("</"),
+                    _ => false,
+                });
+
+                if !tag_like {
+                    bound.bindings.push(Binding { path: path.clone(), target: Target::Data, parts });
+                }
+            }
+        },
+        NodeKind::Element => {
+            for (name, value) in dom.attributes(node) {
+                if !value.contains(MARK_START) {
+                    continue;
+                }
+
+                let parts = parts(&value);
+                match (name.strip_prefix(EVENT_PREFIX), parts.as_slice()) {
+                    // Only a lone placeholder can be a handler
+                    (Some(event), [Part::Slot(slot)]) => {
+                        bound.events.push(EventBinding {
+                            path: path.clone(),
+                            event: event.to_string(),
+                            slot: *slot,
+                        });
+                        dom.remove_attribute(node, &name);
+                    },
+                    _ => bound.bindings.push(Binding {
+                        path: path.clone(),
+                        target: Target::Attribute(name),
+                        parts,
+                    }),
+                }
+            }
+
+            // A `data-ref` with a placeholder in it is just another attribute
+            if let Some(name) = dom.get_attribute(node, REF_ATTRIBUTE) {
+                if !name.contains(MARK_START) {
+                    dom.remove_attribute(node, REF_ATTRIBUTE);
+                    if bound.refs.iter().all(|(_, other)| *other != name) {
+                        bound.refs.push((path.clone(), name));
+                    }
+                }
+            }
+
+            if let Some((_, Mark::Each { items, template, key })) = take_each(dom, node, marks) {
+                bound.lists.push(ListBinding {
+                    path: path.clone(),
+                    items: items.to_string(),
+                    template: template.to_string(),
+                    key: key.as_ref().map(|key| key.to_string()),
+                });
+            }
+        },
+        NodeKind::Fragment | NodeKind::Other => {},
+    }
+
+    for (i, child) in dom.child_nodes(node).iter().enumerate() {
+        path.push(i);
+        find_bindings(dom, child, path, marks, bound);
+        path.pop();
+    }
+}
+
+// Whether there's a `MARK_START` or `MARK_END` anywhere under `node`
+fn has_marks<B: DomBackend>(dom:&B, node:&B::Node) -> bool {
+    let is_mark = |c| c == MARK_START || c == MARK_END;
+    let here = match dom.kind(node) {
+        NodeKind::Text | NodeKind::Comment => dom.text(node).contains(is_mark),
+        NodeKind::Element => dom.attributes(node).iter().any(|(name, value)| name.contains(is_mark) || value.contains(is_mark)),
+        NodeKind::Fragment | NodeKind::Other => false,
+    };
+    here || dom.child_nodes(node).iter().any(|child| has_marks(dom, child))
+}
+
+// Removes the `data-ref`s under `node`, returning the elements that had them
+pub(crate) fn take_refs<B: DomBackend>(dom:&B, node:&B::Node) -> Refs<B::Node> {
+    fn walk<B: DomBackend>(dom:&B, node:&B::Node, refs:&mut Refs<B::Node>) {
+        if dom.kind(node) == NodeKind::Element {
+            if let Some(name) = dom.get_attribute(node, REF_ATTRIBUTE) {
+                dom.remove_attribute(node, REF_ATTRIBUTE);
+                refs.elems.entry(name).or_insert_with(|| node.clone());
+            }
+        }
+        for child in dom.child_nodes(node).iter() {
+            walk(dom, child, refs);
+        }
+    }
+
+    let mut refs = Refs::default();
+    walk(dom, node, &mut refs);
+    refs
+}
+
+// Removes the `${#each ...}` attribute from `elem`, returning the mark it
+// stands for and its index
+fn take_each<'m, 'a, B: DomBackend>(dom:&B, elem:&B::Node, marks:&'m [(usize, Mark<'a>)]) -> Option<(usize, &'m Mark<'a>)> {
+    let value = dom.get_attribute(elem, EACH_ATTRIBUTE)?;
+    dom.remove_attribute(elem, EACH_ATTRIBUTE);
+    let i:usize = value.parse().ok()?;
+    match marks.get(i) {
+        Some((_, mark @ Mark::Each { .. })) => Some((i, mark)),
+        _ => None,
+    }
+}
+
+// Removes the `${#each ...}` attributes under `node`, returning the elements
+// that had them, with the index of the mark
+fn take_lists<B: DomBackend>(dom:&B, node:&B::Node, template:&Template) -> Lists<B::Node> {
+    fn walk<B: DomBackend>(dom:&B, node:&B::Node, template:&Template, lists:&mut Lists<B::Node>) {
+        if dom.kind(node) == NodeKind::Element {
+            if let Some((i, _)) = take_each(dom, node, &template.marks) {
+                lists.push((i, node.clone()));
+            }
+        }
+        for child in dom.child_nodes(node).iter() {
+            walk(dom, child, template, lists);
+        }
+    }
+
+    let mut lists = Vec::new();
+    walk(dom, node, template, &mut lists);
+    lists
+}
+
+// The slots that are only used in `on:event="${key}"` attributes, going by the
+// template text, for renders that don't have the parsed template to go by
+pub(crate) fn handler_only_slots(template:&Template) -> Vec<usize> {
+    let source = &template.template_str;
+    // The placeholder has to be the whole quoted value of an `on:` attribute
+    let is_handler = |start:usize, end:usize| {
+        let quote = match source[end..].chars().next() {
+            Some(quote @ ('"' | '\'')) => quote,
+            _ => return false,
+        };
+        let name = match source[..start].strip_suffix(quote).and_then(|before| before.strip_suffix('=')) {
+            Some(before) => &before[before.rfind(|c:char| c.is_ascii_whitespace()).map_or(0, |i| i + 1)..],
+            None => return false,
+        };
+        name.len() > EVENT_PREFIX.len() && name.starts_with(EVENT_PREFIX)
+    };
+
+    (0..template.keys.len())
+        .filter(|slot| {
+            let mut replaces = template.replaces.iter().filter(|(other, _)| other == slot).peekable();
+            replaces.peek().is_some() && replaces.all(|(_, (start, end))| is_handler(*start, *end))
+        })
+        .collect()
+}
+
+// Removes the `on:event` attributes under `node`, which only instances use
+pub(crate) fn strip_events<B: DomBackend>(dom:&B, node:&B::Node) {
+    if dom.kind(node) == NodeKind::Element {
+        for (name, _) in dom.attributes(node) {
+            if name.starts_with(EVENT_PREFIX) {
+                dom.remove_attribute(node, &name);
+            }
+        }
+    }
+    for child in dom.child_nodes(node).iter() {
+        strip_events(dom, child);
+    }
+}
+
+// Whether `node` is the comment in front of a row, see `ROW_MARKER`
+pub(crate) fn is_row_marker<B: DomBackend>(dom:&B, node:&B::Node) -> bool {
+    dom.kind(node) == NodeKind::Comment && dom.text(node) == ROW_MARKER
+}
+
+// Removes the hydration numbers in and under `nodes`, putting each element in
+// its place in `found`. The rows of lists are numbered separately, so this
+// stops at the first row in each element.
+fn take_marked<B: DomBackend>(dom:&B, nodes:&[B::Node], found:&mut Vec<Option<B::Node>>) {
+    for child in nodes.iter() {
+        if is_row_marker(dom, child) {
+            break;
+        }
+        if dom.kind(child) == NodeKind::Element {
+            if let Some(number) = dom.get_attribute(child, HYDRATE_ATTRIBUTE) {
+                dom.remove_attribute(child, HYDRATE_ATTRIBUTE);
+                if let Some(slot @ None) = number.parse().ok().and_then(|i:usize| found.get_mut(i)) {
+                    *slot = Some(child.clone());
+                }
+            }
+        }
+        take_marked(dom, &dom.child_nodes(child), found);
+    }
+}
+
+// The text to put in place of each placeholder, by slot
+//
+// The `handler_only` slots are only used for event handlers, which don't come
+// from `data`, so they aren't missing if `data` doesn't have them
+pub(crate) fn texts<'v, V: ContextValue>(template:&Template, data:&'v HashMap<&str, V>, options:&RenderOptions, handler_only:&[usize]) -> Result<Vec<Cow<'v, str>>, Errors> {
+    let mut found = Vec::with_capacity(template.keys.len());
+    template.find(data, &mut found);
+
+    if handler_only.is_empty() {
+        template.check(data, &found, options)?;
+    } else {
+        let check_options = RenderOptions {
+            extra: options.extra.clone(),
+            missing: MissingPolicy::Empty,
+        };
+        let mut errors = match template.check(data, &found, &check_options) {
+            Ok(()) => Vec::new(),
+            Err(errors) => errors.inner,
+        };
+
+        if options.missing == MissingPolicy::Fail {
+            for (slot, (key, value)) in template.keys.iter().zip(found.iter()).enumerate() {
+                if value.is_none() && !handler_only.contains(&slot) {
+                    errors.push(Error::Missing(key.to_string()));
+                }
+            }
+        }
+
+        if !errors.is_empty() {
+            return Err(Errors { inner: errors });
+        }
+    }
+
+    let mut values = Vec::with_capacity(found.len());
+    crate::evaluate(&found, &mut values);
+
+    Ok(values
+        .into_iter()
+        .zip(template.keys.iter())
+        .map(|(value, key)| match value {
+            Some(value) => value,
+            None if options.missing == MissingPolicy::Marker => Cow::Owned(marker(key)),
+            None => Cow::Borrowed(""),
+        })
+        .collect())
+}
+
+// What the plain renders and `MissingPolicy::Marker` put in place of a value
+pub(crate) fn marker(key:&str) -> String {
+    format!("${{{}}}", key)
+}
+
+// The element nodes among the children of `frag`
+pub(crate) fn roots<B: DomBackend>(dom:&B, frag:&B::Node) -> Vec<B::Node> {
+    dom.child_nodes(frag)
+        .into_iter()
+        .filter(|node| dom.kind(node) == NodeKind::Element)
+        .collect()
+}
+
+// The only element among top-level `nodes`. Whitespace and comments around
+// it are fine, but other text would be lost.
+pub(crate) fn only_elem<B: DomBackend>(dom:&B, nodes:impl IntoIterator<Item = B::Node>) -> Result<B::Node, Error> {
+    let mut elems = Vec::new();
+    for node in nodes {
+        match dom.kind(&node) {
+            NodeKind::Element => elems.push(node),
+            NodeKind::Text => {
+                let text = dom.text(&node);
+                if !text.trim().is_empty() {
+                    return Err(Error::RootText(text.trim().to_string()));
+                }
+            },
+            _ => {},
+        }
+    }
+
+    if elems.len() != 1 {
+        return Err(Error::Roots(elems.len()));
+    }
+    Ok(elems.remove(0))
+}
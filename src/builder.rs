@@ -0,0 +1,340 @@
+use crate::assemble::Assembler;
+use std::borrow::Cow;
+use crate::scan::{Scanner, Tag, Token};
+use crate::{precompiled, Error, Mark, Template};
+
+/// What to do with whitespace in the template text
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Whitespace {
+    /// Remove leading and trailing whitespace (the default)
+    #[default]
+    Trim,
+    /// Keep the text exactly as it was given, e.g. for plain-text emails or
+    /// `<pre>` blocks
+    Preserve,
+    /// Trim, and also collapse whitespace-only text between two tags: if it
+    /// contains a line break it's removed entirely, otherwise it becomes a
+    /// single space
+    ///
+    /// This is not aware of `<pre>` or `<textarea>`, so don't use it for
+    /// templates where the whitespace between tags matters.
+    Collapse,
+}
+
+// `${ref name}` is shorthand for this attribute, which is only put in for DOM
+// renders
+const REF_START: &str = "ref ";
+pub(crate) const REF_ATTRIBUTE: &str = "data-ref";
+
+// `${#each items template=row key=field}` marks the element that a list's rows
+// go in, which is also only for DOM renders
+const EACH_START: &str = "#each ";
+
+/// Creates a [`Template`] with non-default settings
+///
+/// [`Template::new`] is the same as `Template::builder(s).build()`.
+///
+/// Byte positions in errors always refer to the original input, no matter
+/// what the settings are.
+#[derive(Clone, Debug)]
+pub struct TemplateBuilder<'a> {
+    pub(crate) input: &'a str,
+    pub(crate) whitespace: Whitespace,
+}
+
+impl<'a> TemplateBuilder<'a> {
+    pub fn new(input: &'a str) -> Self {
+        Self {
+            input,
+            whitespace: Whitespace::default(),
+        }
+    }
+
+    pub fn whitespace(mut self, whitespace: Whitespace) -> Self {
+        self.whitespace = whitespace;
+        self
+    }
+
+    pub fn build(self) -> Result<Template<'a>, Error> {
+        let input = self.input;
+
+        // Split the input up into literals and keys. Comments are dropped
+        // entirely, so the literals on either side end up next to each other,
+        // unless they have trim markers
+        let mut pieces = Vec::new();
+
+        // Current position in the input
+        let mut cursor = 0;
+
+        for token in Scanner::new(input) {
+            match token? {
+                Token::Tag(start, end) => {
+                    pieces.push(Piece::Literal(cursor, start));
+                    let tag = Tag::parse(input, start, end);
+                    let inner = &input[tag.key_start..tag.key_end];
+                    if let Some(name) = inner.strip_prefix(REF_START) {
+                        if !is_identifier(name.trim()) {
+                            return Err(Error::InvalidRef(start));
+                        }
+                        let name_start = tag.key_start + REF_START.len() + (name.len() - name.trim_start().len());
+                        pieces.push(Piece::Ref(name_start, name_start + name.trim().len(), tag));
+                    } else if inner.starts_with(EACH_START) {
+                        pieces.push(parse_each(input, start, tag)?);
+                    } else if inner.starts_with('!') {
+                        if tag.trim_before || tag.trim_after {
+                            pieces.push(Piece::Comment(tag));
+                        }
+                    } else {
+                        pieces.push(Piece::Key(start, end, tag));
+                    }
+
+                    // Move cursor to the end of this match
+                    cursor = end;
+                },
+                Token::Raw((open_start, open_end), (close_start, close_end)) => {
+                    pieces.push(Piece::Literal(cursor, open_start));
+                    let open = Tag::parse(input, open_start, open_end);
+                    let close = Tag::parse(input, close_start, close_end);
+
+                    // The markers on the inside of the two tags trim the raw
+                    // text itself, e.g. `${raw -}`
+                    let (mut start, mut end) = (open_end, close_start);
+                    if open.trim_after {
+                        start += input[start..end].len() - input[start..end].trim_start().len();
+                    }
+                    if close.trim_before {
+                        end = start + input[start..end].trim_end().len();
+                    }
+                    pieces.push(Piece::Raw(start, end, open, close));
+                    cursor = close_end;
+                }
+            }
+        }
+
+        pieces.push(Piece::Literal(cursor, input.len()));
+
+        // Strip whitespace around placeholders and blocks with `-` markers
+        for i in 0..pieces.len() {
+            let (trim_before, trim_after) = pieces[i].markers();
+            if trim_before {
+                trim_end(input, pieces[..i].iter_mut().rev());
+            }
+            if trim_after {
+                trim_start(input, pieces[(i + 1)..].iter_mut());
+            }
+        }
+
+        // A `${ref name}` or `${#each ...}` leaves nothing in the text, so drop
+        // the space that was in front of it if it would be doubled or left at
+        // the end of the tag, e.g. `<input ${ref name} value="">` or
+        // `<input ${ref name}>`
+        for i in 1..pieces.len().saturating_sub(1) {
+            if !matches!(pieces[i], Piece::Ref(..) | Piece::Each { .. }) {
+                continue;
+            }
+            if let (Piece::Literal(before_start, before_end), Piece::Literal(after_start, after_end)) = (&pieces[i - 1], &pieces[i + 1]) {
+                let after = &input[*after_start..*after_end];
+                if input[*before_start..*before_end].ends_with(' ') && (after.starts_with(' ') || after.starts_with('>') || after.starts_with("/>")) {
+                    pieces[i - 1] = Piece::Literal(*before_start, before_end - " ".len());
+                }
+            }
+        }
+
+        if self.whitespace != Whitespace::Preserve {
+            trim_start(input, pieces.iter_mut());
+            trim_end(input, pieces.iter_mut().rev());
+        }
+
+        let mut output = Assembler::new(input);
+        let mut template = Template::empty(Default::default());
+        template.source_hash = precompiled::source_hash(input, self.whitespace);
+
+        for piece in pieces {
+            match piece {
+                Piece::Literal(start, end) => self.push_literal(&mut output, start, end),
+                Piece::Raw(start, end, ..) => output.push(start, end),
+                Piece::Comment(_) => {},
+                Piece::Ref(start, end, _) => {
+                    template.marks.push((output.len(), Mark::Ref(Cow::Borrowed(&input[start..end]))));
+                },
+                Piece::Each { items, template: row, key, .. } => {
+                    let span = |(start, end): (usize, usize)| Cow::Borrowed(&input[start..end]);
+                    let items = span(items);
+                    template.push_key(items.clone());
+                    template.marks.push((output.len(), Mark::Each {
+                        // The rows' template is named after the list, unless
+                        // it's given
+                        template: row.map_or_else(|| items.clone(), span),
+                        items,
+                        key: key.map(span),
+                    }));
+                },
+                Piece::Key(start, end, tag) => {
+                    let new_start = output.len();
+                    if tag.trim_before || tag.trim_after {
+                        // Normalize to a plain `${key}`, so that the markers
+                        // don't show up in `render_plain`
+                        output.push_str("${");
+                        output.push(tag.key_start, tag.key_end);
+                        output.push_str("}");
+                    } else {
+                        output.push(start, end);
+                    }
+                    template.push_replace(Cow::Borrowed(&input[tag.key_start..tag.key_end]), new_start, output.len());
+                }
+            }
+        }
+
+        template.template_str = output.finish();
+
+        Ok(template)
+    }
+
+    // Pushes the literal text at `input[start..end]`, applying the whitespace
+    // setting
+    fn push_literal(&self, output: &mut Assembler<'a>, start: usize, end: usize) {
+        if self.whitespace != Whitespace::Collapse {
+            output.push(start, end);
+            return;
+        }
+
+        let bytes = self.input.as_bytes();
+        let mut cursor = start;
+        let mut i = start;
+
+        while i < end {
+            if bytes[i] == b'>' {
+                let run_start = i + 1;
+                let run_end = run_start
+                    + bytes[run_start..end]
+                        .iter()
+                        .take_while(|b| b.is_ascii_whitespace())
+                        .count();
+
+                if run_end > run_start && run_end < end && bytes[run_end] == b'<' {
+                    output.push(cursor, run_start);
+                    if !bytes[run_start..run_end].contains(&b'\n') {
+                        output.push_str(" ");
+                    }
+                    cursor = run_end;
+                }
+                i = run_end;
+            } else {
+                i += 1;
+            }
+        }
+
+        output.push(cursor, end);
+    }
+}
+
+enum Piece {
+    // (start, end) in the input
+    Literal(usize, usize),
+    // (start, end) of the whole `${...}` in the input
+    Key(usize, usize, Tag),
+    // (start, end) in the input of the text inside `${raw}...${/raw}`, which
+    // is kept exactly as it is, and the two tags
+    Raw(usize, usize, Tag, Tag),
+    // (start, end) in the input of the name in a `${ref name}`
+    Ref(usize, usize, Tag),
+    // (start, end) in the input of the parts of a
+    // `${#each items template=row key=field}`
+    Each {
+        items: (usize, usize),
+        template: Option<(usize, usize)>,
+        key: Option<(usize, usize)>,
+        tag: Tag,
+    },
+    // A `${! ...}` with trim markers, which is only kept for those
+    Comment(Tag),
+}
+
+impl Piece {
+    // Whether to strip the whitespace before and after this piece
+    fn markers(&self) -> (bool, bool) {
+        match self {
+            Piece::Literal(..) => (false, false),
+            Piece::Raw(_, _, open, close) => (open.trim_before, close.trim_after),
+            Piece::Key(_, _, tag) | Piece::Ref(_, _, tag) | Piece::Each { tag, .. } | Piece::Comment(tag) => (tag.trim_before, tag.trim_after),
+        }
+    }
+}
+
+// Letters, digits, `_` and `-`, not starting with a digit or `-`
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-'),
+        _ => false,
+    }
+}
+
+// Parses `${#each items}`, optionally followed by `template=row` and
+// `key=field` in either order, where `start` is where the whole tag starts
+fn parse_each(input: &str, start: usize, tag: Tag) -> Result<Piece, Error> {
+    let mut items = None;
+    let mut template = None;
+    let mut key = None;
+
+    let inner_start = tag.key_start + EACH_START.len();
+    let inner = &input[inner_start..tag.key_end];
+    for word in inner.split_ascii_whitespace() {
+        let word_start = inner_start + (word.as_ptr() as usize - inner.as_ptr() as usize);
+        let (option, value) = match word.find('=') {
+            Some(i) => (Some(&word[..i]), (word_start + i + "=".len(), word_start + word.len())),
+            None => (None, (word_start, word_start + word.len())),
+        };
+
+        let part = match option {
+            None if items.is_none() => &mut items,
+            Some("template") if items.is_some() && template.is_none() => &mut template,
+            Some("key") if items.is_some() && key.is_none() => &mut key,
+            _ => return Err(Error::InvalidEach(start)),
+        };
+        if value.0 == value.1 {
+            return Err(Error::InvalidEach(start));
+        }
+        *part = Some(value);
+    }
+
+    match items {
+        Some(items) => Ok(Piece::Each { items, template, key, tag }),
+        None => Err(Error::InvalidEach(start)),
+    }
+}
+
+// Strips leading whitespace from the literals, until reaching a key or some
+// text that isn't whitespace. Comments are skipped over, as they leave nothing
+// in the text
+fn trim_start<'p>(input: &str, pieces: impl Iterator<Item = &'p mut Piece>) {
+    for piece in pieces {
+        match piece {
+            Piece::Literal(start, end) => {
+                let literal = &input[*start..*end];
+                *start += literal.len() - literal.trim_start().len();
+                if start != end {
+                    break;
+                }
+            },
+            Piece::Comment(_) => {},
+            Piece::Key(..) | Piece::Raw(..) | Piece::Ref(..) | Piece::Each { .. } => break,
+        }
+    }
+}
+
+// Same as `trim_start`, but going backwards
+fn trim_end<'p>(input: &str, pieces: impl Iterator<Item = &'p mut Piece>) {
+    for piece in pieces {
+        match piece {
+            Piece::Literal(start, end) => {
+                *end = *start + input[*start..*end].trim_end().len();
+                if start != end {
+                    break;
+                }
+            },
+            Piece::Comment(_) => {},
+            Piece::Key(..) | Piece::Raw(..) | Piece::Ref(..) | Piece::Each { .. } => break,
+        }
+    }
+}
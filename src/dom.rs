@@ -0,0 +1,328 @@
+//! Rendering straight to the browser's DOM, with the `wasm` feature
+
+use crate::backend::{DomBackend, NodeKind};
+use crate::bind::{self, Refs};
+use crate::{ContextValue, Error, Errors, Handlers, RenderOptions, Template, TemplateCache, TemplateInstance};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use web_sys::{Document, DocumentFragment, Element, Event, HtmlElement, HtmlTemplateElement, Node};
+
+/// [`DomBackend`] for the browser's DOM
+#[derive(Clone)]
+pub struct WebDom {
+    doc: Document,
+}
+
+impl WebDom {
+    pub fn new(doc:Document) -> Self {
+        Self { doc }
+    }
+
+    pub fn document(&self) -> &Document {
+        &self.doc
+    }
+}
+
+impl DomBackend for WebDom {
+    type Node = Node;
+    type Handler = Closure<dyn FnMut(Event)>;
+
+    fn parse_fragment(&self, html:&str) -> Node {
+        let el: HtmlTemplateElement = self.doc.create_element("template").unwrap_throw().unchecked_into();
+        el.set_inner_html(html);
+        el.content().into()
+    }
+
+    // Imported rather than cloned, so the nodes belong to `doc` and not the
+    // template's inert document
+    fn clone_node(&self, node:&Node) -> Node {
+        self.doc.import_node_with_deep(node, true).unwrap_throw()
+    }
+
+    fn kind(&self, node:&Node) -> NodeKind {
+        match node.node_type() {
+            Node::ELEMENT_NODE => NodeKind::Element,
+            Node::TEXT_NODE => NodeKind::Text,
+            Node::COMMENT_NODE => NodeKind::Comment,
+            Node::DOCUMENT_FRAGMENT_NODE => NodeKind::Fragment,
+            _ => NodeKind::Other,
+        }
+    }
+
+    fn create_text(&self, text:&str) -> Node {
+        self.doc.create_text_node(text).into()
+    }
+
+    fn child_nodes(&self, node:&Node) -> Vec<Node> {
+        let children = node.child_nodes();
+        (0..children.length()).filter_map(|i| children.item(i)).collect()
+    }
+
+    fn child(&self, node:&Node, index:usize) -> Option<Node> {
+        node.child_nodes().item(index as u32)
+    }
+
+    fn insert_before(&self, parent:&Node, node:&Node, reference:Option<&Node>) {
+        parent.insert_before(node, reference).unwrap_throw();
+    }
+
+    fn remove(&self, node:&Node) {
+        if let Some(parent) = node.parent_node() {
+            parent.remove_child(node).unwrap_throw();
+        }
+    }
+
+    fn attributes(&self, node:&Node) -> Vec<(String, String)> {
+        let attributes = node.unchecked_ref::<Element>().attributes();
+        (0..attributes.length())
+            .filter_map(|i| attributes.item(i))
+            .map(|attribute| (attribute.name(), attribute.value()))
+            .collect()
+    }
+
+    fn get_attribute(&self, node:&Node, name:&str) -> Option<String> {
+        node.unchecked_ref::<Element>().get_attribute(name)
+    }
+
+    fn set_attribute(&self, node:&Node, name:&str, value:&str) {
+        node.unchecked_ref::<Element>().set_attribute(name, value).unwrap_throw();
+    }
+
+    fn remove_attribute(&self, node:&Node, name:&str) {
+        node.unchecked_ref::<Element>().remove_attribute(name).unwrap_throw();
+    }
+
+    fn text(&self, node:&Node) -> String {
+        node.node_value().unwrap_or_default()
+    }
+
+    fn set_text(&self, node:&Node, text:&str) {
+        node.set_node_value(Some(text));
+    }
+
+    fn add_listener(&self, node:&Node, event:&str, handler:&Self::Handler) {
+        node.add_event_listener_with_callback(event, handler.as_ref().unchecked_ref()).unwrap_throw();
+    }
+
+    fn remove_listener(&self, node:&Node, event:&str, handler:&Self::Handler) {
+        node.remove_event_listener_with_callback(event, handler.as_ref().unchecked_ref()).unwrap_throw();
+    }
+}
+
+impl Handlers<WebDom> {
+    /// Adds `f` as the handler for `key`
+    pub fn on(mut self, key:impl Into<String>, f:impl FnMut(Event) + 'static) -> Self {
+        self.insert(key, Closure::wrap(Box::new(f) as Box<dyn FnMut(Event)>));
+        self
+    }
+}
+
+impl <N: JsCast + Clone> Refs<N> {
+    /// The element called `name` as a more specific type, e.g.
+    /// `HtmlInputElement`
+    ///
+    /// Returns `None` if there's no such element or it's some other type.
+    pub fn get_as<T: JsCast>(&self, name:&str) -> Option<T> {
+        self.get(name)?.clone().dyn_into().ok()
+    }
+}
+
+impl <'a> Template <'a> {
+    /// Renders the template the same way as [`Self::render`], and parses the
+    /// HTML into a fragment of `doc`
+    ///
+    /// `data-ref` and `on:event` attributes are left out, see [`Handlers`].
+    /// So are the rows of `${#each items}` lists, since they're rendered with
+    /// other templates, which the [`TemplateCache`] functions can find.
+    pub fn render_fragment<V: ContextValue>(&self, doc:&Document, data:&HashMap<&str, V>) -> Result<DocumentFragment, Errors> {
+        self.render_fragment_with(doc, data, &RenderOptions::strict())
+    }
+
+    pub fn render_fragment_with<V: ContextValue>(&self, doc:&Document, data:&HashMap<&str, V>, options:&RenderOptions) -> Result<DocumentFragment, Errors> {
+        self.render_fragment_refs_with(doc, data, options).map(|(frag, _)| frag)
+    }
+
+    pub fn render_fragment_plain(&self, doc:&Document) -> DocumentFragment {
+        let texts:Vec<Cow<str>> = self.keys.iter().map(|key| Cow::Owned(bind::marker(key))).collect();
+        parse(doc, self, &texts).0
+    }
+
+    /// Same as [`Self::render_fragment`], but also returns the elements that
+    /// were marked with `data-ref="name"` or `${ref name}`
+    ///
+    /// The `data-ref` attributes are removed from the fragment. All the DOM
+    /// render functions do that, these ones just also return the elements.
+    pub fn render_fragment_refs<V: ContextValue>(&self, doc:&Document, data:&HashMap<&str, V>) -> Result<(DocumentFragment, Refs<Element>), Errors> {
+        self.render_fragment_refs_with(doc, data, &RenderOptions::strict())
+    }
+
+    pub fn render_fragment_refs_with<V: ContextValue>(&self, doc:&Document, data:&HashMap<&str, V>, options:&RenderOptions) -> Result<(DocumentFragment, Refs<Element>), Errors> {
+        let texts = bind::texts(self, data, options, &bind::handler_only_slots(self))?;
+        Ok(parse(doc, self, &texts))
+    }
+
+    /// Renders a template that has exactly one top-level element
+    ///
+    /// Whitespace and comments around the element are ignored. Other text is
+    /// an [`Error::RootText`], and any other number of elements is an
+    /// [`Error::Roots`], see [`Self::render_elems`] for templates with several
+    /// top-level elements.
+    pub fn render_elem<V: ContextValue>(&self, doc:&Document, data:&HashMap<&str, V>) -> Result<HtmlElement, Errors> {
+        self.render_elem_with(doc, data, &RenderOptions::strict())
+    }
+
+    pub fn render_elem_with<V: ContextValue>(&self, doc:&Document, data:&HashMap<&str, V>, options:&RenderOptions) -> Result<HtmlElement, Errors> {
+        let frag = self.render_fragment_with(doc, data, options)?;
+        single_root(&frag).map_err(|e| Errors { inner: vec![e] })
+    }
+
+    pub fn render_elem_plain(&self, doc:&Document) -> Result<HtmlElement, Error> {
+        single_root(&self.render_fragment_plain(doc))
+    }
+
+    /// Renders the template and returns each of its top-level elements, in
+    /// order
+    ///
+    /// Text and comments between the elements are dropped.
+    pub fn render_elems<V: ContextValue>(&self, doc:&Document, data:&HashMap<&str, V>) -> Result<Vec<Element>, Errors> {
+        self.render_elems_with(doc, data, &RenderOptions::strict())
+    }
+
+    pub fn render_elems_with<V: ContextValue>(&self, doc:&Document, data:&HashMap<&str, V>, options:&RenderOptions) -> Result<Vec<Element>, Errors> {
+        self.render_fragment_with(doc, data, options).map(|frag| roots(&frag))
+    }
+
+    pub fn render_elems_plain(&self, doc:&Document) -> Vec<Element> {
+        roots(&self.render_fragment_plain(doc))
+    }
+}
+
+/// Same as the [`Template`] functions, for the template called `name`
+///
+/// These go through a [`DomRenderer<WebDom>`](crate::DomRenderer), which also
+/// has the `render_fragment_text` functions. Those parse each template once and
+/// set the values as text on clones of it after that.
+impl <'a> TemplateCache <'a> {
+    pub fn render_fragment<V: ContextValue>(&self, name:&str, data:&HashMap<&str, V>) -> Result<DocumentFragment, Errors> {
+        self.render_fragment_with(name, data, &self.options)
+    }
+
+    pub fn render_fragment_with<V: ContextValue>(&self, name:&str, data:&HashMap<&str, V>, options:&RenderOptions) -> Result<DocumentFragment, Errors> {
+        self.dom.render_fragment_with(self, name, data, options).map(JsCast::unchecked_into)
+    }
+
+    pub fn render_fragment_plain(&self, name:&str) -> Result<DocumentFragment, Error> {
+        self.dom.render_fragment_plain(self, name).map(JsCast::unchecked_into)
+    }
+
+    pub fn render_fragment_refs<V: ContextValue>(&self, name:&str, data:&HashMap<&str, V>) -> Result<(DocumentFragment, Refs<Element>), Errors> {
+        self.render_fragment_refs_with(name, data, &self.options)
+    }
+
+    pub fn render_fragment_refs_with<V: ContextValue>(&self, name:&str, data:&HashMap<&str, V>, options:&RenderOptions) -> Result<(DocumentFragment, Refs<Element>), Errors> {
+        let (frag, refs) = self.dom.render_fragment_refs_with(self, name, data, options)?;
+        Ok((frag.unchecked_into(), refs.map(JsCast::unchecked_into)))
+    }
+
+    /// See [`DomRenderer::render_fragment_text`](crate::DomRenderer::render_fragment_text)
+    pub fn render_fragment_text<V: ContextValue>(&self, name:&str, data:&HashMap<&str, V>) -> Result<DocumentFragment, Errors> {
+        self.render_fragment_text_with(name, data, &self.options)
+    }
+
+    pub fn render_fragment_text_with<V: ContextValue>(&self, name:&str, data:&HashMap<&str, V>, options:&RenderOptions) -> Result<DocumentFragment, Errors> {
+        self.dom.render_fragment_text_with(self, name, data, options).map(JsCast::unchecked_into)
+    }
+
+    pub fn render_fragment_text_refs<V: ContextValue>(&self, name:&str, data:&HashMap<&str, V>) -> Result<(DocumentFragment, Refs<Element>), Errors> {
+        self.render_fragment_text_refs_with(name, data, &self.options)
+    }
+
+    pub fn render_fragment_text_refs_with<V: ContextValue>(&self, name:&str, data:&HashMap<&str, V>, options:&RenderOptions) -> Result<(DocumentFragment, Refs<Element>), Errors> {
+        let (frag, refs) = self.dom.render_fragment_text_refs_with(self, name, data, options)?;
+        Ok((frag.unchecked_into(), refs.map(JsCast::unchecked_into)))
+    }
+
+    pub fn render_elem<V: ContextValue>(&self, name:&str, data:&HashMap<&str, V>) -> Result<HtmlElement, Errors> {
+        self.render_elem_with(name, data, &self.options)
+    }
+
+    pub fn render_elem_with<V: ContextValue>(&self, name:&str, data:&HashMap<&str, V>, options:&RenderOptions) -> Result<HtmlElement, Errors> {
+        let frag = self.render_fragment_with(name, data, options)?;
+        single_root(&frag).map_err(|e| Errors { inner: vec![e] })
+    }
+
+    pub fn render_elem_plain(&self, name:&str) -> Result<HtmlElement, Error> {
+        single_root(&self.render_fragment_plain(name)?)
+    }
+
+    pub fn render_elems<V: ContextValue>(&self, name:&str, data:&HashMap<&str, V>) -> Result<Vec<Element>, Errors> {
+        self.render_elems_with(name, data, &self.options)
+    }
+
+    pub fn render_elems_with<V: ContextValue>(&self, name:&str, data:&HashMap<&str, V>, options:&RenderOptions) -> Result<Vec<Element>, Errors> {
+        self.render_fragment_with(name, data, options).map(|frag| roots(&frag))
+    }
+
+    pub fn render_elems_plain(&self, name:&str) -> Result<Vec<Element>, Error> {
+        Ok(roots(&self.render_fragment_plain(name)?))
+    }
+
+    /// See [`DomRenderer::render_instance`](crate::DomRenderer::render_instance)
+    pub fn render_instance<V: ContextValue>(&self, name:&str, data:&HashMap<&str, V>) -> Result<TemplateInstance<WebDom>, Errors> {
+        self.dom.render_instance(self, name, data)
+    }
+
+    pub fn render_instance_with<V: ContextValue>(&self, name:&str, data:&HashMap<&str, V>, options:&RenderOptions) -> Result<TemplateInstance<WebDom>, Errors> {
+        self.dom.render_instance_with(self, name, data, options)
+    }
+
+    pub fn render_instance_handlers<V: ContextValue>(&self, name:&str, data:&HashMap<&str, V>, handlers:Handlers<WebDom>) -> Result<TemplateInstance<WebDom>, Errors> {
+        self.dom.render_instance_handlers(self, name, data, handlers)
+    }
+
+    pub fn render_instance_handlers_with<V: ContextValue>(&self, name:&str, data:&HashMap<&str, V>, handlers:Handlers<WebDom>, options:&RenderOptions) -> Result<TemplateInstance<WebDom>, Errors> {
+        self.dom.render_instance_handlers_with(self, name, data, handlers, options)
+    }
+
+    /// See [`DomRenderer::hydrate`](crate::DomRenderer::hydrate)
+    pub fn hydrate<V: ContextValue>(&self, name:&str, container:&Element, data:&HashMap<&str, V>) -> Result<TemplateInstance<WebDom>, Errors> {
+        self.dom.hydrate(self, name, container, data)
+    }
+
+    pub fn hydrate_with<V: ContextValue>(&self, name:&str, container:&Element, data:&HashMap<&str, V>, options:&RenderOptions) -> Result<TemplateInstance<WebDom>, Errors> {
+        self.dom.hydrate_with(self, name, container, data, options)
+    }
+
+    pub fn hydrate_handlers<V: ContextValue>(&self, name:&str, container:&Element, data:&HashMap<&str, V>, handlers:Handlers<WebDom>) -> Result<TemplateInstance<WebDom>, Errors> {
+        self.dom.hydrate_handlers(self, name, container, data, handlers)
+    }
+
+    pub fn hydrate_handlers_with<V: ContextValue>(&self, name:&str, container:&Element, data:&HashMap<&str, V>, handlers:Handlers<WebDom>, options:&RenderOptions) -> Result<TemplateInstance<WebDom>, Errors> {
+        self.dom.hydrate_handlers_with(self, name, container, data, handlers, options)
+    }
+}
+
+// Renders `template` with `texts` and parses it, see `bind::parse`, leaving
+// the lists empty
+fn parse(doc:&Document, template:&Template, texts:&[Cow<str>]) -> (DocumentFragment, Refs<Element>) {
+    let (frag, refs, _) = bind::parse(&WebDom::new(doc.clone()), template, texts);
+    (frag.unchecked_into(), refs.map(JsCast::unchecked_into))
+}
+
+// `children` only has the element nodes, unlike `child_nodes`
+fn roots(frag:&DocumentFragment) -> Vec<Element> {
+    let children = frag.children();
+    (0..children.length())
+        .filter_map(|i| children.item(i))
+        .collect()
+}
+
+fn single_root(frag:&DocumentFragment) -> Result<HtmlElement, Error> {
+    let dom = WebDom::new(frag.owner_document().unwrap_throw());
+    bind::only_elem(&dom, dom.child_nodes(frag))?
+        // e.g. an `<svg>` at the top level is an element, but not an HTML one
+        .dyn_into::<HtmlElement>()
+        .map_err(|el| Error::NotHtmlElement(el.node_name().to_lowercase()))
+}
@@ -0,0 +1,166 @@
+//! Custom elements that show a template, with the `wasm` feature
+
+use crate::{TemplateCache, TemplateInstance, WebDom};
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::rc::Rc;
+use wasm_bindgen::prelude::*;
+use web_sys::HtmlElement;
+
+// Custom elements have to be JS classes, so this one just hands each
+// callback over to Rust. Elements are numbered when they're connected, so
+// the Rust side can keep their instances without holding on to the elements.
+#[wasm_bindgen(inline_js = r#"
+export function define_element(tag, observed, connect, changed, disconnect) {
+    customElements.define(tag, class extends HTMLElement {
+        static get observedAttributes() { return observed; }
+        constructor() { super(); this.attachShadow({ mode: "open" }); }
+        connectedCallback() { this._sht = connect(this); }
+        attributeChangedCallback() { if (this._sht !== undefined) changed(this, this._sht); }
+        disconnectedCallback() {
+            if (this._sht !== undefined) {
+                disconnect(this._sht);
+                this._sht = undefined;
+            }
+        }
+    });
+}
+"#)]
+extern "C" {
+    #[wasm_bindgen(catch)]
+    fn define_element(
+        tag:&str,
+        observed:Box<[JsValue]>,
+        connect:&Closure<dyn FnMut(HtmlElement) -> u32>,
+        changed:&Closure<dyn FnMut(HtmlElement, u32)>,
+        disconnect:&Closure<dyn FnMut(u32)>,
+    ) -> Result<(), JsValue>;
+}
+
+/// A custom element, e.g. `<app-card>`, that renders a template from a
+/// [`TemplateCache`] into its shadow root
+///
+/// The template's values come from the element's attributes, and it's updated
+/// in place whenever one of those attributes changes. Attributes that aren't
+/// set are empty.
+///
+/// ```no_run
+/// # use simple_html_template::{CustomElement, TemplateCache};
+/// # use std::rc::Rc;
+/// let cache = Rc::new(TemplateCache::new(&[
+///     ("card", r#"<h1>${title}</h1><p class="${kind}"><slot></slot></p>"#),
+/// ]));
+///
+/// // <app-card title="Hello" kind="wide">...</app-card>
+/// CustomElement::new("app-card", "card").define(cache).unwrap();
+/// ```
+pub struct CustomElement {
+    tag: String,
+    template: String,
+    // (attribute, key)
+    attributes: Vec<(String, String)>,
+}
+
+impl CustomElement {
+    /// `tag` has to be a valid custom element name, i.e. have a `-` in it
+    pub fn new(tag:impl Into<String>, template:impl Into<String>) -> Self {
+        Self {
+            tag: tag.into(),
+            template: template.into(),
+            attributes: Vec::new(),
+        }
+    }
+
+    /// Passes the value of `attribute` to the template as `key`
+    ///
+    /// Without any of these, each of the template's keys comes from the
+    /// attribute with the same name. Since HTML attribute names are lowercase,
+    /// keys with capitals in them need this.
+    pub fn attribute(mut self, attribute:impl Into<String>, key:impl Into<String>) -> Self {
+        self.attributes.push((attribute.into(), key.into()));
+        self
+    }
+
+    /// Registers the element with the browser
+    ///
+    /// Each element renders with [`TemplateCache::render_instance`], which
+    /// uses the cache's options. Errors from rendering are thrown from the
+    /// element's callbacks, since there's nowhere to return them to.
+    ///
+    /// This fails if the browser doesn't accept the name, e.g. because it's
+    /// already defined. Definitions are permanent, so the closures that are
+    /// registered are never freed.
+    ///
+    /// Panics if the template doesn't exist.
+    pub fn define(self, cache:Rc<TemplateCache<'static>>) -> Result<(), JsValue> {
+        let template_name = self.template;
+        let attributes = match self.attributes.is_empty() {
+            true => cache.templates.get(template_name.as_str()).unwrap_throw()
+                .keys()
+                .iter()
+                .map(|key| (key.to_string(), key.to_string()))
+                .collect(),
+            false => self.attributes,
+        };
+        let attributes = Rc::new(attributes);
+        let observed:Box<[JsValue]> = attributes.iter().map(|(attribute, _)| JsValue::from_str(attribute)).collect();
+
+        let instances:Rc<RefCell<HashMap<u32, TemplateInstance<WebDom>>>> = Default::default();
+        let next_id = Rc::new(Cell::new(0u32));
+
+        let connect = {
+            let instances = instances.clone();
+            let attributes = attributes.clone();
+            let cache = cache.clone();
+            Closure::wrap(Box::new(move |element:HtmlElement| {
+                let shadow = element.shadow_root().unwrap_throw();
+                let data = values(&element, &attributes);
+                let instance = cache.render_instance(&template_name, &data).unwrap_or_else(|e| wasm_bindgen::throw_str(&e.to_string()));
+
+                // A reconnected element starts over, since its old instance
+                // was dropped when it was disconnected. Nothing is borrowed
+                // here, because appending can connect nested elements.
+                shadow.set_inner_html("");
+                shadow.append_child(instance.fragment()).unwrap_throw();
+
+                let id = next_id.get();
+                next_id.set(id.wrapping_add(1));
+                instances.borrow_mut().insert(id, instance);
+                id
+            }) as Box<dyn FnMut(HtmlElement) -> u32>)
+        };
+
+        let changed = {
+            let instances = instances.clone();
+            Closure::wrap(Box::new(move |element:HtmlElement, id:u32| {
+                // Taken out while it's updated, for the same reason
+                let instance = instances.borrow_mut().remove(&id);
+                if let Some(mut instance) = instance {
+                    let result = instance.update(&values(&element, &attributes));
+                    instances.borrow_mut().insert(id, instance);
+                    result.unwrap_or_else(|e| wasm_bindgen::throw_str(&e.to_string()));
+                }
+            }) as Box<dyn FnMut(HtmlElement, u32)>)
+        };
+
+        let disconnect = Closure::wrap(Box::new(move |id:u32| {
+            let instance = instances.borrow_mut().remove(&id);
+            drop(instance);
+        }) as Box<dyn FnMut(u32)>);
+
+        define_element(&self.tag, observed, &connect, &changed, &disconnect)?;
+
+        connect.forget();
+        changed.forget();
+        disconnect.forget();
+        Ok(())
+    }
+}
+
+// The template's values, from the element's attributes
+fn values<'a>(element:&HtmlElement, attributes:&'a [(String, String)]) -> HashMap<&'a str, String> {
+    attributes
+        .iter()
+        .map(|(attribute, key)| (key.as_str(), element.get_attribute(attribute).unwrap_or_default()))
+        .collect()
+}
@@ -31,6 +31,14 @@ impl fmt::Display for Errors {
             }
         });
 
+        let nesteds = self.inner.iter().filter_map(|e| {
+            if let Error::Nested(i) = e {
+                Some(i)
+            } else {
+                None
+            }
+        });
+
         let missings = self.inner.iter().filter_map(|e| {
             if let Error::Missing(k) = e {
                 Some(k)
@@ -47,43 +55,64 @@ impl fmt::Display for Errors {
             }
         });
 
-        let uncloseds_count = uncloseds.clone().count();
-        let missings_count = missings.clone().count();
-        let extras_count = extras.clone().count();
+        let mut written = false;
 
-        format_error(
+        written |= format_error(
             f,
+            written,
             "unclosed delimiter opened at byte",
             uncloseds,
-            uncloseds_count,
             false,
         )?;
 
-        if uncloseds_count > 0 && missings_count > 0 {
-            write!(f, "; ")?;
-        }
+        written |= format_error(
+            f,
+            written,
+            "nested delimiter opened at byte",
+            nesteds,
+            false,
+        )?;
 
-        format_error(f, "missing key", missings, missings_count, true)?;
+        written |= format_error(f, written, "missing key", missings, true)?;
 
-        if (uncloseds_count > 0 || missings_count > 0) && extras_count > 0 {
-            write!(f, "; ")?;
-        }
+        written |= format_error(f, written, "extraneous key", extras, true)?;
 
-        format_error(f, "extraneous key", extras, extras_count, true)?;
+        // Everything else is rare enough to just be listed one by one
+        for e in self.inner.iter() {
+            match e {
+                Error::Unclosed(_) | Error::Nested(_) | Error::Missing(_) | Error::Extra(_) => {},
+                _ => {
+                    if written {
+                        write!(f, "; ")?;
+                    }
+                    write!(f, "{}", e)?;
+                    written = true;
+                }
+            }
+        }
 
         Ok(())
     }
 }
 
 // This is also awful but I don't think it can really be better
-fn format_error(
+//
+// Returns whether anything was written, so the caller knows whether the next
+// group needs a separator
+fn format_error<T: fmt::Display>(
     f: &mut fmt::Formatter<'_>,
+    separate: bool,
     problem: &str,
-    iter: impl Iterator<Item = impl fmt::Display>,
-    count: usize,
+    iter: impl Iterator<Item = T> + Clone,
     quotes: bool,
-) -> fmt::Result {
+) -> Result<bool, fmt::Error> {
+    let count = iter.clone().count();
+
     if count > 0 {
+        if separate {
+            write!(f, "; ")?;
+        }
+
         let s = if count != 1 {
             "s"
         } else {
@@ -112,7 +141,7 @@ fn format_error(
         }
     }
 
-    Ok(())
+    Ok(count > 0)
 }
 
 /// A single specific error
@@ -134,6 +163,84 @@ pub enum Error {
     /// Holds the zero-indexed byte position of the beginning of the opening
     /// delimiter
     Unclosed(usize),
+
+    /// A key-begin delimiter was found inside of a key, before the previous
+    /// one was closed
+    ///
+    /// Holds the zero-indexed byte position of the beginning of the inner
+    /// opening delimiter
+    Nested(usize),
+
+    /// A template was requested by name from a cache that doesn't have it
+    ///
+    /// Holds the requested name
+    UnknownTemplate(String),
+
+    /// A template was added to a cache under a name that was already taken
+    ///
+    /// Holds the full name, including any namespace
+    DuplicateTemplate(String),
+
+    /// A DOM render that needs a single top-level element found a different
+    /// number of them
+    ///
+    /// Holds the number of top-level elements that were found
+    Roots(usize),
+
+    /// A DOM render that needs a single top-level element found text next to
+    /// it, which would be dropped
+    ///
+    /// Holds the text, without the whitespace around it
+    RootText(String),
+
+    /// A DOM render that needs an HTML element got some other kind of
+    /// element, e.g. an `<svg>`
+    ///
+    /// Holds the tag name of the element
+    NotHtmlElement(String),
+
+    /// A template was rendered into a `TemplateInstance`, but one of its
+    /// placeholders isn't in text, a comment or an attribute value (e.g.
+    /// `<div ${attrs}>`), so it can't be updated in place
+    ///
+    /// Holds the key of the placeholder
+    Unbindable(String),
+
+    /// The name in a `${ref name}` wasn't made of letters, digits, `_` and
+    /// `-`, starting with a letter or `_`
+    ///
+    /// Holds the zero-indexed byte position of the `${`
+    InvalidRef(usize),
+
+    /// A `${#each ...}` wasn't a list name, optionally followed by
+    /// `template=name` and `key=field`, each at most once
+    ///
+    /// Holds the zero-indexed byte position of the `${`
+    InvalidEach(usize),
+
+    /// Two items in a keyed list had the same key
+    ///
+    /// Holds the key
+    DuplicateKey(String),
+
+    /// A DOM render was given a value for a `${#each key}` list that isn't a
+    /// [`Value::List`](crate::Value::List)
+    ///
+    /// Holds the key
+    NotAList(String),
+
+    /// A value for a hydratable render would have ended the comment,
+    /// `<script>` or `<style>` it's in, which can't be escaped, or made a
+    /// comment that hydration would take for the start of a list row
+    ///
+    /// Holds the key
+    UnsafeValue(String),
+
+    /// A template was hydrated, but the DOM it was given doesn't match what
+    /// the server would have rendered
+    ///
+    /// Holds a description of what couldn't be found
+    Hydration(String),
 }
 
 impl fmt::Display for Error {
@@ -144,6 +251,68 @@ impl fmt::Display for Error {
             Self::Unclosed(i) => {
                 write!(f, "unclosed delimitor opened at byte {}", i)
             }
+            Self::Nested(i) => {
+                write!(f, "nested delimiter opened at byte {}", i)
+            }
+            Self::UnknownTemplate(name) => write!(f, "unknown template \"{}\"", name),
+            Self::DuplicateTemplate(name) => write!(f, "duplicate template \"{}\"", name),
+            Self::Roots(n) => write!(f, "expected a single root element, found {}", n),
+            Self::RootText(text) => write!(f, "text outside the root element: \"{}\"", text),
+            Self::NotHtmlElement(tag) => write!(f, "root element <{}> is not an HTML element", tag),
+            Self::Unbindable(k) => write!(f, "placeholder for key \"{}\" can't be bound to the DOM", k),
+            Self::InvalidRef(i) => write!(f, "invalid ${{ref}} name at byte {}", i),
+            Self::InvalidEach(i) => write!(f, "invalid ${{#each}} at byte {}", i),
+            Self::DuplicateKey(k) => write!(f, "duplicate list key \"{}\"", k),
+            Self::NotAList(k) => write!(f, "value for \"{}\" isn't a list", k),
+            Self::UnsafeValue(k) => write!(f, "value for \"{}\" would end the comment or raw text element it's in, or look like a row marker", k),
+            Self::Hydration(what) => write!(f, "server-rendered DOM doesn't match the template: {}", what),
+        }
+    }
+}
+
+/// Errors from loading a precompiled template, see
+/// [`Template::from_bytes`](crate::Template::from_bytes)
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum LoadError {
+    /// The data doesn't start with the expected header, so it probably isn't
+    /// a precompiled template (or it's a cache where a template was expected,
+    /// or vice versa)
+    Magic,
+
+    /// The data was written by an incompatible version of this crate
+    ///
+    /// Holds the format version that was found
+    Version(u16),
+
+    /// The data is truncated or otherwise malformed
+    Corrupt,
+
+    /// The source text has changed since the template was precompiled
+    ///
+    /// Holds the name of the template, if it came from a cache
+    SourceMismatch(Option<String>),
+
+    /// A precompiled cache has two templates with the same name
+    ///
+    /// Holds the name
+    DuplicateTemplate(String),
+}
+
+impl ErrorTrait for LoadError {}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Magic => write!(f, "not a precompiled template"),
+            Self::Version(v) => write!(f, "unsupported precompiled format version {}", v),
+            Self::Corrupt => write!(f, "precompiled template is corrupt"),
+            Self::SourceMismatch(Some(name)) => {
+                write!(f, "source of template \"{}\" changed since it was precompiled", name)
+            }
+            Self::SourceMismatch(None) => {
+                write!(f, "source changed since the template was precompiled")
+            }
+            Self::DuplicateTemplate(name) => write!(f, "duplicate template \"{}\" in precompiled cache", name),
         }
     }
 }
@@ -0,0 +1,113 @@
+//! Rendering on the server and picking the DOM back up in the browser
+
+use crate::backend::{DomBackend, NodeKind};
+use crate::bind::{self, Rendered};
+use crate::renderer;
+use crate::{ContextValue, DomRenderer, Error, Errors, Handlers, MemoryDom, MemoryNode, RenderOptions, TemplateCache, TemplateInstance};
+use std::collections::HashMap;
+
+/// Server-side rendering for [`DomRenderer::hydrate`]
+impl DomRenderer<MemoryDom> {
+    /// Renders the template called `name` to HTML that can be hydrated in the
+    /// browser
+    ///
+    /// This is the same HTML as [`Template::render`](crate::Template::render)
+    /// gives, escaped as text and attribute values, except that the elements
+    /// the client needs to find have a `data-sht` attribute with a number, and
+    /// `data-ref` and `on:event` attributes are left out. The values are set
+    /// as text, as in [`Self::render_fragment_text`], so they shouldn't be
+    /// escaped beforehand. Comments and `<script>` and `<style>` elements
+    /// can't be escaped, so values that would end them (e.g. `-->` in a
+    /// comment, or `</script>`) are an
+    /// [`Error::UnsafeValue`](crate::Error::UnsafeValue).
+    ///
+    /// The rows of `${#each items}` lists are rendered the same way, each
+    /// after a `<!--sht-row-->` comment, with their own `data-sht` numbers.
+    ///
+    /// Templates with placeholders that can't be filled in directly fail with
+    /// an [`Error::Unbindable`](crate::Error::Unbindable), the same as
+    /// [`Self::render_instance`].
+    pub fn render_hydratable<V: ContextValue>(&self, cache:&TemplateCache, name:&str, data:&HashMap<&str, V>) -> Result<String, Errors> {
+        self.render_hydratable_with(cache, name, data, &cache.options)
+    }
+
+    pub fn render_hydratable_with<V: ContextValue>(&self, cache:&TemplateCache, name:&str, data:&HashMap<&str, V>, options:&RenderOptions) -> Result<String, Errors> {
+        self.hydratable(cache, name, data, options).map(|frag| frag.to_html())
+    }
+
+    // The nodes for `render_hydratable`, with the rows of the lists in them
+    fn hydratable<V: ContextValue>(&self, cache:&TemplateCache, name:&str, data:&HashMap<&str, V>, options:&RenderOptions) -> Result<MemoryNode, Errors> {
+        let dom = self.backend();
+        let template = renderer::find(cache, name).map_err(|e| Errors { inner: vec![e] })?;
+        let parsed = self.parsed(name, template);
+        let bound = parsed.bound()?;
+        let texts = bind::texts(&parsed.template, data, options, &bound.handler_only_slots())?;
+
+        let frag = bound.clone_content(dom);
+        let mut errors = Vec::new();
+        for binding in bound.bindings.iter() {
+            let node = binding.node(dom, &frag);
+            binding.set(dom, &node, &texts);
+
+            // A comment that looks like a row marker would throw off hydration
+            if dom.kind(&node) != NodeKind::Element && (node.breaks_out(&dom.text(&node), binding.slots().map(|slot| texts[slot].as_ref())) || bind::is_row_marker(dom, &node)) {
+                let key = &parsed.template.keys[binding.slots().next().unwrap()];
+                errors.push(Error::UnsafeValue(key.to_string()));
+            }
+        }
+        if !errors.is_empty() {
+            return Err(Errors { inner: errors });
+        }
+        bound.mark(dom, &frag);
+
+        let lists = bound.lists.iter().map(|list| (list.items.as_str(), list.template.as_str(), list.key.as_deref(), list.node(dom, &frag)));
+        self.render_rows(cache, name, lists, data, |template, row| {
+            let nodes = self.hydratable(cache, template, row, options)?;
+            let marker = dom.child(&dom.parse_fragment(&format!("<!--{}-->", bind::ROW_MARKER)), 0).unwrap();
+            dom.insert_before(&nodes, &marker, dom.child(&nodes, 0).as_ref());
+            Ok(nodes)
+        })?;
+
+        Ok(frag)
+    }
+}
+
+impl <B: DomBackend + Clone> DomRenderer<B> {
+    /// Makes a [`TemplateInstance`] out of nodes that were parsed from
+    /// [`DomRenderer::render_hydratable`], instead of rendering new ones
+    ///
+    /// The server's HTML has to be the only content of `container`. The nodes
+    /// stay where they are, and get the `data-sht` attributes taken off and
+    /// the values set again from `data`, which would normally be the same
+    /// values the server used. If the nodes don't line up with the template,
+    /// e.g. because the HTML came from a different template, this fails with
+    /// an [`Error::Hydration`](crate::Error::Hydration). The same goes for the
+    /// rows of lists, which are matched up with the items in `data` in order,
+    /// so there has to be one row for each item.
+    ///
+    /// Text that the browser's parser moves around, like text directly inside
+    /// a `<table>`, can't be hydrated.
+    pub fn hydrate<V: ContextValue>(&self, cache:&TemplateCache, name:&str, container:&B::Node, data:&HashMap<&str, V>) -> Result<TemplateInstance<B>, Errors> {
+        self.hydrate_with(cache, name, container, data, &cache.options)
+    }
+
+    pub fn hydrate_with<V: ContextValue>(&self, cache:&TemplateCache, name:&str, container:&B::Node, data:&HashMap<&str, V>, options:&RenderOptions) -> Result<TemplateInstance<B>, Errors> {
+        self.hydrate_handlers_with(cache, name, container, data, Handlers::new(), options)
+    }
+
+    /// Same as [`Self::hydrate`], and also adds the event listeners, see
+    /// [`Self::render_instance_handlers`]
+    pub fn hydrate_handlers<V: ContextValue>(&self, cache:&TemplateCache, name:&str, container:&B::Node, data:&HashMap<&str, V>, handlers:Handlers<B>) -> Result<TemplateInstance<B>, Errors> {
+        self.hydrate_handlers_with(cache, name, container, data, handlers, &cache.options)
+    }
+
+    pub fn hydrate_handlers_with<V: ContextValue>(&self, cache:&TemplateCache, name:&str, container:&B::Node, data:&HashMap<&str, V>, handlers:Handlers<B>, options:&RenderOptions) -> Result<TemplateInstance<B>, Errors> {
+        let schema = self.schema(cache, name).map_err(|e| Errors { inner: vec![e] })?;
+        let rendered = Rendered {
+            parent: container,
+            nodes: self.backend().child_nodes(container),
+            next: None,
+        };
+        TemplateInstance::hydrate(self.backend(), &schema, name, rendered, data, handlers, options)
+    }
+}
@@ -0,0 +1,342 @@
+use crate::backend::{DomBackend, NodeKind};
+use crate::bind::{self, Bound, Located, Parsed, Refs, Rendered};
+use crate::list;
+use crate::{ContextValue, Error, Errors, ExtraPolicy, KeyedList, MissingPolicy, RenderOptions, Template};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+// The parsed templates that an instance and the rows of its lists, and of
+// theirs, are rendered with, by full name
+pub(crate) type Schema<B> = Rc<HashMap<String, SchemaEntry<B>>>;
+
+pub(crate) struct SchemaEntry<B: DomBackend> {
+    pub(crate) parsed: Parsed<B>,
+    // The full name of the template for the rows of each list, by the name
+    // that the template gives
+    pub(crate) rows: HashMap<String, String>,
+}
+
+// Makes the handlers for a row from its key
+pub(crate) type RowHandlers<B> = Box<dyn FnMut(&str) -> Handlers<B>>;
+
+/// Closures for the `on:event="${key}"` attributes in a template, by key
+///
+/// These are given to
+/// [`DomRenderer::render_instance_handlers`](crate::DomRenderer::render_instance_handlers)
+/// next to the values, rather than in them, since the values are text. The
+/// other DOM render functions leave `on:` attributes out of the output, and
+/// don't expect values for keys that are only used in them.
+///
+/// The rows of a `${#each items}` list get their handlers from a closure
+/// that's called with the key of each new row, see [`Self::rows`].
+pub struct Handlers<B: DomBackend> {
+    closures: HashMap<String, B::Handler>,
+    rows: HashMap<String, RowHandlers<B>>,
+}
+
+impl <B: DomBackend> Handlers<B> {
+    pub fn new() -> Self {
+        Self {
+            closures: HashMap::new(),
+            rows: HashMap::new(),
+        }
+    }
+
+    pub fn insert(&mut self, key:impl Into<String>, handler:B::Handler) {
+        self.closures.insert(key.into(), handler);
+    }
+
+    /// Uses `f` to make the handlers for each new row of the list under
+    /// `items`, from the row's key
+    ///
+    /// The list keeps `f` for the rows that are added when the instance is
+    /// updated.
+    pub fn rows(mut self, items:impl Into<String>, f:impl FnMut(&str) -> Handlers<B> + 'static) -> Self {
+        self.rows.insert(items.into(), Box::new(f));
+        self
+    }
+
+    // Checks for handlers that aren't used, and ones that are missing, like
+    // `Template::resolve` does for values
+    fn check(&self, template:&Template, event_slots:&[usize], lists:&[bind::ListBinding], options:&RenderOptions) -> Vec<Error> {
+        let mut errors = Vec::new();
+
+        let unused_handlers = self.closures.keys().filter(|key| !matches!(template.slots.get(key.as_str()), Some(slot) if event_slots.contains(slot)));
+        let unused_rows = self.rows.keys().filter(|items| lists.iter().all(|list| list.items != **items));
+        for key in unused_handlers.chain(unused_rows) {
+            match &options.extra {
+                ExtraPolicy::Ignore => {},
+                ExtraPolicy::Fail => errors.push(Error::Extra(key.clone())),
+                ExtraPolicy::Warn(f) => f(key),
+            }
+        }
+
+        if options.missing == MissingPolicy::Fail {
+            for slot in event_slots {
+                let key = &template.keys[*slot];
+                if !self.closures.contains_key(key.as_ref()) {
+                    errors.push(Error::Missing(key.to_string()));
+                }
+            }
+        }
+
+        errors
+    }
+}
+
+impl <B: DomBackend> Default for Handlers<B> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A rendered template that can be updated in place
+///
+/// Made by [`DomRenderer::render_instance`](crate::DomRenderer::render_instance).
+/// The instance holds on to the nodes that the placeholders ended up in, so
+/// [`Self::update`] only touches the text and attributes whose values actually
+/// changed. Everything else, like focus or event listeners, is left alone.
+pub struct TemplateInstance<B: DomBackend> {
+    dom: B,
+    template: Rc<Template<'static>>,
+    bound: Rc<Bound<B>>,
+    options: RenderOptions,
+    fragment: B::Node,
+    // The top-level nodes, which are still around after they've been moved
+    // out of the fragment
+    roots: Vec<B::Node>,
+    // The node for each binding in `bound`
+    targets: Vec<B::Node>,
+    // The current text of each slot
+    texts: Vec<String>,
+    refs: Refs<B::Node>,
+    handlers: Handlers<B>,
+    // (element, event, handler key) for each listener that was added
+    listeners: Vec<(B::Node, String, String)>,
+    lists: Vec<KeyedList<B>>,
+}
+
+// What an update does to an instance, worked out before anything is changed
+pub(crate) struct Prepared<'v, B: DomBackend> {
+    texts: Vec<Cow<'v, str>>,
+    lists: Vec<list::Prepared<'v, B>>,
+}
+
+impl <B: DomBackend + Clone> TemplateInstance<B> {
+    pub(crate) fn new<V: ContextValue>(dom:&B, schema:&Schema<B>, name:&str, data:&HashMap<&str, V>, handlers:Handlers<B>, options:&RenderOptions) -> Result<Self, Errors> {
+        let instance = Self::attach(dom, schema, name, data, handlers, options, |bound, _| {
+            let fragment = bound.clone_content(dom);
+            let located = bound.locate(dom, &fragment);
+            Ok((fragment, located))
+        })?;
+        instance.fill_lists(|dom, list| list.prepare(dom, data, options).map(|prepared| list.apply(dom, prepared)))
+    }
+
+    // Picks up the nodes of a render for hydration, including the rows of its
+    // lists. The instance's fragment is the parent of the nodes.
+    pub(crate) fn hydrate<V: ContextValue>(dom:&B, schema:&Schema<B>, name:&str, rendered:Rendered<B::Node>, data:&HashMap<&str, V>, handlers:Handlers<B>, options:&RenderOptions) -> Result<Self, Errors> {
+        let instance = Self::attach(dom, schema, name, data, handlers, options, |bound, template| {
+            let fragment = rendered.parent.clone();
+            let located = bound.hydrate(dom, rendered, &template.keys)?;
+            Ok((fragment, located))
+        })?;
+        instance.fill_lists(|dom, list| list.hydrate(dom, data, options))
+    }
+
+    // Checks the values and handlers, then gets the nodes from `nodes` and
+    // fills them in. The lists are left empty.
+    fn attach<V: ContextValue>(
+        dom:&B,
+        schema:&Schema<B>,
+        name:&str,
+        data:&HashMap<&str, V>,
+        mut handlers:Handlers<B>,
+        options:&RenderOptions,
+        nodes:impl FnOnce(&Bound<B>, &Template) -> Result<(B::Node, Located<B::Node>), Error>,
+    ) -> Result<Self, Errors> {
+        let entry = schema.get(name).ok_or_else(|| Errors { inner: vec![Error::UnknownTemplate(name.to_string())] })?;
+        let bound = entry.parsed.bound()?.clone();
+        let template = entry.parsed.template.clone();
+
+        // Collect all the errors before bailing out, as in `Template::render`
+        let event_slots = bound.event_slots();
+        let (texts, mut errors) = match bind::texts(&template, data, options, &bound.handler_only_slots()) {
+            Ok(texts) => (texts, Vec::new()),
+            Err(errors) => (Vec::new(), errors.inner),
+        };
+        errors.extend(handlers.check(&template, &event_slots, &bound.lists, options));
+        if !errors.is_empty() {
+            return Err(Errors { inner: errors });
+        }
+
+        let (fragment, located) = nodes(&bound, &template).map_err(|e| Errors { inner: vec![e] })?;
+        for (binding, node) in bound.bindings.iter().zip(located.targets.iter()) {
+            binding.set(dom, node, &texts);
+        }
+
+        let mut listeners = Vec::new();
+        for (event, node) in bound.events.iter().zip(located.events) {
+            let key = &template.keys[event.slot];
+            if let Some(handler) = handlers.closures.get(key.as_ref()) {
+                dom.add_listener(&node, &event.event, handler);
+                listeners.push((node, event.event.clone(), key.to_string()));
+            }
+        }
+
+        let mut lists = Vec::with_capacity(bound.lists.len());
+        for (list, node) in bound.lists.iter().zip(located.lists) {
+            let row_handlers = handlers.rows.remove(&list.items);
+            let row = entry.rows[&list.template].clone();
+            lists.push(KeyedList::new(node, list, row, schema.clone(), row_handlers));
+        }
+
+        let texts = texts.into_iter().map(Cow::into_owned).collect();
+
+        Ok(Self {
+            dom: dom.clone(),
+            template,
+            bound,
+            options: options.clone(),
+            fragment,
+            roots: located.roots,
+            targets: located.targets,
+            texts,
+            refs: located.refs,
+            handlers,
+            listeners,
+            lists,
+        })
+    }
+}
+
+impl <B: DomBackend + Clone> TemplateInstance<B> {
+    // Adds the rows of each list with `f`, collecting the errors
+    fn fill_lists(mut self, mut f:impl FnMut(&B, &mut KeyedList<B>) -> Result<(), Errors>) -> Result<Self, Errors> {
+        let mut errors = Vec::new();
+        for list in self.lists.iter_mut() {
+            if let Err(e) = f(&self.dom, list) {
+                errors.extend(e.inner);
+            }
+        }
+
+        match errors.is_empty() {
+            true => Ok(self),
+            false => Err(Errors { inner: errors }),
+        }
+    }
+}
+
+impl <B: DomBackend> TemplateInstance<B> {
+    /// The rendered nodes, to be inserted into the document
+    ///
+    /// The fragment is empty once it's been inserted, but the instance still
+    /// works, since it keeps its own references to the nodes. For a hydrated
+    /// instance, this is the container it was hydrated in.
+    pub fn fragment(&self) -> &B::Node {
+        &self.fragment
+    }
+
+    /// The top-level nodes, including text and comments
+    pub fn nodes(&self) -> &[B::Node] {
+        &self.roots
+    }
+
+    /// The top-level elements
+    pub fn elems(&self) -> Vec<B::Node> {
+        self.roots.iter().filter(|node| self.dom.kind(node) == NodeKind::Element).cloned().collect()
+    }
+
+    /// The one top-level element, see
+    /// [`DomRenderer::render_elem`](crate::DomRenderer::render_elem)
+    pub fn elem(&self) -> Result<B::Node, Error> {
+        bind::only_elem(&self.dom, self.roots.iter().cloned())
+    }
+
+    /// The elements that were marked with `data-ref="name"` or
+    /// `${ref name}`, see
+    /// [`DomRenderer::render_fragment_refs`](crate::DomRenderer::render_fragment_refs)
+    pub fn refs(&self) -> &Refs<B::Node> {
+        &self.refs
+    }
+
+    /// The list from the `${#each items}` in the template, by the key of its
+    /// items
+    ///
+    /// If several lists use the same key, this is the first one.
+    pub fn list(&self, items:&str) -> Option<&KeyedList<B>> {
+        self.lists.iter().find(|list| list.items() == items)
+    }
+}
+
+impl <B: DomBackend + Clone> TemplateInstance<B> {
+    /// Sets the values again, using the options the instance was rendered with
+    ///
+    /// The rows of each list are matched up with its new items, see
+    /// [`KeyedList`]. If anything fails, nothing is changed.
+    pub fn update<V: ContextValue>(&mut self, data:&HashMap<&str, V>) -> Result<(), Errors> {
+        let options = self.options.clone();
+        self.update_with(data, &options)
+    }
+
+    pub fn update_with<V: ContextValue>(&mut self, data:&HashMap<&str, V>, options:&RenderOptions) -> Result<(), Errors> {
+        let prepared = self.prepare(data, options)?;
+        self.apply(prepared);
+        Ok(())
+    }
+
+    // Everything `update` needs that can fail, without changing anything
+    pub(crate) fn prepare<'v, V: ContextValue>(&mut self, data:&'v HashMap<&str, V>, options:&RenderOptions) -> Result<Prepared<'v, B>, Errors> {
+        let (texts, mut errors) = match bind::texts(&self.template, data, options, &self.bound.handler_only_slots()) {
+            Ok(texts) => (texts, Vec::new()),
+            Err(errors) => (Vec::new(), errors.inner),
+        };
+
+        let mut lists = Vec::with_capacity(self.lists.len());
+        for list in self.lists.iter_mut() {
+            match list.prepare(&self.dom, data, options) {
+                Ok(prepared) => lists.push(prepared),
+                Err(e) => errors.extend(e.inner),
+            }
+        }
+
+        if !errors.is_empty() {
+            return Err(Errors { inner: errors });
+        }
+        Ok(Prepared { texts, lists })
+    }
+
+    pub(crate) fn apply(&mut self, prepared:Prepared<B>) {
+        for (list, prepared) in self.lists.iter_mut().zip(prepared.lists) {
+            list.apply(&self.dom, prepared);
+        }
+
+        let texts = prepared.texts;
+        let changed:Vec<bool> = self.texts.iter().zip(texts.iter()).map(|(old, new)| old != new).collect();
+        if !changed.contains(&true) {
+            return;
+        }
+
+        for (binding, node) in self.bound.bindings.iter().zip(self.targets.iter()) {
+            if binding.slots().any(|slot| changed[slot]) {
+                binding.set(&self.dom, node, &texts);
+            }
+        }
+
+        for (slot, text) in texts.iter().enumerate() {
+            if changed[slot] {
+                self.texts[slot] = text.to_string();
+            }
+        }
+    }
+}
+
+// The handlers are dropped right after this, and in the browser calling a
+// dropped closure throws, so the listeners have to go first
+impl <B: DomBackend> Drop for TemplateInstance<B> {
+    fn drop(&mut self) {
+        for (node, event, key) in self.listeners.iter() {
+            self.dom.remove_listener(node, event, &self.handlers.closures[key]);
+        }
+    }
+}
@@ -0,0 +1,62 @@
+use crate::Template;
+
+/// A piece of a template, see [`Template::segments`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Segment<'t> {
+    /// Text that is output as-is
+    Literal(&'t str),
+    /// A `${key}` that is replaced when rendering
+    Key(&'t str),
+}
+
+/// A single `${key}` in a template, see [`Template::placeholders`]
+///
+/// The span is in bytes, relative to [`Template::render_plain`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Placeholder<'t> {
+    pub key: &'t str,
+    /// Points to the `$` in the `${`
+    pub start: usize,
+    /// Just after the matching `}`
+    pub end: usize,
+}
+
+/// Iterator returned by [`Template::segments`]
+pub struct Segments<'t> {
+    pub(crate) template: &'t Template<'t>,
+    // Index into the placeholders
+    pub(crate) index: usize,
+    // Byte position in the template string
+    pub(crate) cursor: usize,
+}
+
+impl<'t> Iterator for Segments<'t> {
+    type Item = Segment<'t>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let template_str: &'t str = &self.template.template_str;
+
+        match self.template.replaces.get(self.index) {
+            Some((slot, (start, end))) => {
+                if self.cursor < *start {
+                    let literal = &template_str[self.cursor..*start];
+                    self.cursor = *start;
+                    Some(Segment::Literal(literal))
+                } else {
+                    self.cursor = *end;
+                    self.index += 1;
+                    Some(Segment::Key(&self.template.keys[*slot]))
+                }
+            },
+            None => {
+                if self.cursor < template_str.len() {
+                    let literal = &template_str[self.cursor..];
+                    self.cursor = template_str.len();
+                    Some(Segment::Literal(literal))
+                } else {
+                    None
+                }
+            }
+        }
+    }
+}
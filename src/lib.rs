@@ -1,16 +1,52 @@
+use std::borrow::Cow;
 use std::collections::HashMap;
+mod assemble;
+mod backend;
+mod batch;
+mod bind;
+mod builder;
+#[cfg(feature = "wasm")]
+mod dom;
+#[cfg(feature = "wasm")]
+mod element;
 mod errors;
+mod hydrate;
+mod instance;
+mod introspect;
+mod list;
+mod memory;
+mod namespace;
+mod options;
+pub mod precompiled;
+mod renderer;
+mod scan;
+mod shared;
+mod value;
 #[cfg(test)]
 mod tests;
 
 #[cfg(feature = "wasm")]
 use wasm_bindgen::prelude::*;
 #[cfg(feature = "wasm")]
-use wasm_bindgen::JsCast;
-#[cfg(feature = "wasm")]
-use web_sys::{Document, DocumentFragment, HtmlTemplateElement, HtmlElement};
+use web_sys::Document;
 
-pub use errors::{Error, Errors};
+pub use backend::{DomBackend, NodeKind};
+pub use bind::Refs;
+pub use builder::{TemplateBuilder, Whitespace};
+#[cfg(feature = "wasm")]
+pub use dom::WebDom;
+#[cfg(feature = "wasm")]
+pub use element::CustomElement;
+pub use errors::{Error, Errors, LoadError};
+pub use instance::{Handlers, TemplateInstance};
+pub use introspect::{Placeholder, Segment, Segments};
+pub use list::KeyedList;
+pub use memory::{MemoryDom, MemoryNode};
+pub use namespace::SEPARATOR;
+pub use options::{ExtraPolicy, MissingPolicy, RenderOptions};
+pub use renderer::DomRenderer;
+pub use shared::{SharedTemplateCache, Snapshot};
+pub use value::{ContextValue, Value};
 
 #[macro_export]
 macro_rules! hash_map(
@@ -51,100 +87,211 @@ macro_rules! html_map_strong(
      };
 );
 
+#[derive(Clone, Debug)]
 pub struct Template<'a> {
-    // Stores (key, (key_start, key_end))
-    pub replaces: Vec<(&'a str, (usize, usize))>,
-    pub template_str:&'a str,
+    // Distinct keys, in the order they first appear
+    keys: Vec<Cow<'a, str>>,
+    // Maps each key to its index in `keys`
+    slots: HashMap<Cow<'a, str>, usize>,
+    // Stores (slot, (key_start, key_end))
+    replaces: Vec<(usize, (usize, usize))>,
+    // Borrowed from the input, unless produced by `partial_render`
+    template_str: Cow<'a, str>,
+    // Stores (position, mark), in order, for what only DOM renders put in
+    marks: Vec<(usize, Mark<'a>)>,
+    // Hash of the text the template was parsed from, see `precompiled`
+    source_hash: u64,
+}
+
+// Something that isn't in `template_str`, but that DOM renders put in at its
+// position
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub(crate) enum Mark<'a> {
+    // A `${ref name}`, which becomes a `data-ref` attribute
+    Ref(Cow<'a, str>),
+    // A `${#each items template=row key=field}`, where `items` is also one
+    // of the keys
+    Each {
+        items: Cow<'a, str>,
+        template: Cow<'a, str>,
+        key: Option<Cow<'a, str>>,
+    },
+}
+
+impl Mark<'_> {
+    fn into_owned(self) -> Mark<'static> {
+        let owned = |s:Cow<str>| Cow::Owned(s.into_owned());
+        match self {
+            Mark::Ref(name) => Mark::Ref(owned(name)),
+            Mark::Each { items, template, key } => Mark::Each {
+                items: owned(items),
+                template: owned(template),
+                key: key.map(owned),
+            },
+        }
+    }
 }
 
 
 impl <'a> Template <'a> {
     pub fn new (template_str: &'a str) -> Result<Self, Error> {
-        let template_str = template_str.trim();
-        let mut template = Self { replaces: Vec::new(), template_str };
-
-        let replaces = &mut template.replaces;
-
-        // Current position in the format string
-        let mut cursor = 0;
-
-        while cursor < template_str.len() {
-            if let Some(start) = (&template_str[cursor..]).find("${") {
-                let start = start + cursor;
-                if let Some(end) = (&template_str[cursor..]).find('}') {
-                    let end = end + cursor;
-                    replaces.push((
-                        // The extracted key
-                        &template_str[(start + "${".len())..end],
-                        (
-                            // Points to the `$` in the `${`
-                            start,
-                            // Just after the matching `}`
-                            (end + "}".len()),
-                        ),
-                    ));
-
-                    // Move cursor to the end of this match
-                    cursor = end + "}".len();
-                } else {
-                    // Bail immediately: if there's an unclosed delimiter, then
-                    // we basically can't guess about what provided key-value
-                    // pairs are needed
-                    return Err(Error::Unclosed(start));
-                }
-            } else {
-                // No more matches
-                break;
+        Self::builder(template_str).build()
+    }
+
+    /// For creating a template with non-default settings, like keeping
+    /// leading and trailing whitespace
+    pub fn builder (template_str: &'a str) -> TemplateBuilder<'a> {
+        TemplateBuilder::new(template_str)
+    }
+
+    fn empty(template_str: Cow<'a, str>) -> Self {
+        Self {
+            keys: Vec::new(),
+            slots: HashMap::new(),
+            replaces: Vec::new(),
+            marks: Vec::new(),
+            source_hash: 0,
+            template_str,
+        }
+    }
+
+    // A copy of just the keys, for checking values against without holding on
+    // to the text
+    pub(crate) fn keys_only(&self) -> Template<'static> {
+        let mut template = Template::empty(Cow::Borrowed(""));
+        for key in self.keys.iter() {
+            let key:Cow<'static, str> = Cow::Owned(key.to_string());
+            template.slots.insert(key.clone(), template.keys.len());
+            template.keys.push(key);
+        }
+        template.source_hash = self.source_hash;
+        template
+    }
+
+    fn push_replace(&mut self, key: Cow<'a, str>, start: usize, end: usize) {
+        let slot = self.push_key(key);
+        self.replaces.push((slot, (start, end)));
+    }
+
+    // The slot for `key`, which is added if it's new
+    fn push_key(&mut self, key: Cow<'a, str>) -> usize {
+        match self.slots.get(&key) {
+            Some(slot) => *slot,
+            None => {
+                self.keys.push(key.clone());
+                self.slots.insert(key, self.keys.len() - 1);
+                self.keys.len() - 1
             }
         }
-        Ok(template)
     }
 
-    pub fn render<V: AsRef<str>>(&self, vars:&HashMap<&str, V>) -> Result<String, Errors> {
+    pub fn render<V: ContextValue>(&self, vars:&HashMap<&str, V>) -> Result<String, Errors> {
+        self.render_with(vars, &RenderOptions::strict())
+    }
+
+    /// Like [`Self::render`] but with control over how extra and missing keys
+    /// are treated
+    pub fn render_with<V: ContextValue>(&self, vars:&HashMap<&str, V>, options:&RenderOptions) -> Result<String, Errors> {
+        let mut found = Vec::with_capacity(self.keys.len());
+        let mut values = Vec::with_capacity(self.keys.len());
+        self.resolve(vars, options, &mut found, &mut values)?;
+
+        let mut output = String::with_capacity(self.output_len(&values, options));
+        self.write(&values, options, &mut output);
+
+        Ok(output)
+    }
+
+    // Looks up the value for each slot, checking for missing and extra keys
+    // before any lazy values are computed, so that they aren't if the render
+    // fails anyway
+    fn resolve<'v, V: ContextValue>(&self, vars:&'v HashMap<&str, V>, options:&RenderOptions, found:&mut Vec<Option<&'v V>>, values:&mut Vec<Option<Cow<'v, str>>>) -> Result<(), Errors> {
+        self.find(vars, found);
+        self.check(vars, found, options)?;
+        evaluate(found, values);
+        Ok(())
+    }
+
+    // Looks up each distinct key exactly once, no matter how many times it's
+    // used
+    fn find<'v, V>(&self, vars:&'v HashMap<&str, V>, found:&mut Vec<Option<&'v V>>) {
+        found.clear();
+        found.extend(self.keys.iter().map(|k| vars.get(k.as_ref())));
+    }
+
+    // Checks for missing and extra keys, given what `find` found
+    fn check<V>(&self, vars:&HashMap<&str, V>, found:&[Option<&V>], options:&RenderOptions) -> Result<(), Errors> {
         let mut errors = Vec::new();
-        let replaces = &self.replaces;
-        let template_str = &self.template_str;
 
-        for k in vars.keys() {
-            if !replaces.iter().any(|(x, (_, _))| x == k) {
-                errors.push(Error::Extra((*k).to_string()));
+        // If every entry in `vars` was used then there can't be any extras, so
+        // the common case doesn't need to look at `vars` at all
+        let used = found.iter().filter(|v| v.is_some()).count();
+        if used != vars.len() {
+            for k in vars.keys() {
+                if !self.slots.contains_key(*k) {
+                    match &options.extra {
+                        ExtraPolicy::Ignore => {},
+                        ExtraPolicy::Fail => errors.push(Error::Extra((*k).to_string())),
+                        ExtraPolicy::Warn(f) => f(k),
+                    }
+                }
+            }
+        }
+
+        if options.missing == MissingPolicy::Fail {
+            for (k, v) in self.keys.iter().zip(found.iter()) {
+                if v.is_none() {
+                    errors.push(Error::Missing(k.to_string()));
+                }
             }
         }
 
         // Wait on bailing out if there are errors so we can display all the errors
         // at once instead of making the user have to try to fix it twice.
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(Errors {
+                inner: errors,
+            })
+        }
+    }
 
+    // Size of the output, given the values from `resolve`
+    fn output_len(&self, values:&[Option<Cow<str>>], options:&RenderOptions) -> usize {
         // Calculate the size of the text to be added (vs) and the amount of space
-        // the keys take up in the original text (ks)
-        let (ks, vs) = replaces.iter().fold((0, 0), |(ka, va), (k, _)| {
-            if let Some(v) = vars.get(k) {
-                (ka + k.len(), va + v.as_ref().len())
-            } else {
-                errors.push(Error::Missing((*k).to_string()));
-
-                // This is mostly just to get past the typechecker
-                (ka, va)
+        // the placeholders take up in the original text (ks)
+        let (ks, vs) = self.replaces.iter().fold((0, 0), |(ka, va), (slot, (start, end))| {
+            let placeholder_len = end - start;
+            match &values[*slot] {
+                Some(v) => (ka + placeholder_len, va + v.len()),
+                None if options.missing == MissingPolicy::Marker => (ka + placeholder_len, va + placeholder_len),
+                None => (ka + placeholder_len, va),
             }
         });
 
-        // If there were errors, bail out
-        if !errors.is_empty() {
-            return Err(Errors {
-                inner: errors,
-            });
-        }
+        self.template_str.len() + vs - ks
+    }
 
-        let final_len = (template_str.len() - ("${}".len() * replaces.len())) + vs - ks;
+    // Appends the rendered template to `output`, given the values from
+    // `resolve`
+    fn write(&self, values:&[Option<Cow<str>>], options:&RenderOptions, output:&mut String) {
+        let template_str = &self.template_str;
 
-        let mut output = String::with_capacity(final_len);
+        #[cfg(test)]
+        let initial_len = output.len();
 
         let mut cursor:usize = 0;
 
-        for (key, (start, end)) in replaces.into_iter() {
+        for (slot, (start, end)) in self.replaces.iter() {
             output.push_str(&template_str[cursor..*start]);
-            // Unwrapping should be safe at this point because we should have caught
-            // it while calculating replace_size.
-            output.push_str(vars.get(key).unwrap().as_ref());
+            match &values[*slot] {
+                Some(v) => output.push_str(v),
+                // Anything else was caught in `resolve`
+                None => if options.missing == MissingPolicy::Marker {
+                    output.push_str(&template_str[*start..*end]);
+                }
+            }
             cursor = *end;
         }
 
@@ -154,106 +301,206 @@ impl <'a> Template <'a> {
         }
 
         #[cfg(test)]
-        assert_eq!(output.len(), final_len);
-
-        Ok(output)
+        assert_eq!(output.len() - initial_len, self.output_len(values, options));
     }
     
     pub fn render_plain(&self) -> &str {
-        self.template_str 
+        &self.template_str
     }
 
+    /// The distinct keys used by the template, in the order they first appear
+    pub fn keys(&self) -> &[Cow<'a, str>] {
+        &self.keys
+    }
 
-    #[cfg(feature = "wasm")]
-    pub fn render_fragment<V: AsRef<str>>(&self, doc:&Document, data:&HashMap<&str, V>) -> Result<DocumentFragment, Errors> {
-        let html = self.render(data)?;
-        let el: HtmlTemplateElement = doc.create_element("template").unwrap_throw().unchecked_into();
-        el.set_inner_html(&html);
-        Ok(el.content())
+    /// Every `${key}` in the template, in order, including repeats
+    pub fn placeholders(&self) -> impl Iterator<Item = Placeholder<'_>> {
+        self.replaces.iter().map(move |(slot, (start, end))| Placeholder {
+            key: &self.keys[*slot],
+            start: *start,
+            end: *end,
+        })
     }
 
-    #[cfg(feature = "wasm")]
-    pub fn render_fragment_plain(&self, doc:&Document) -> DocumentFragment {
-        let el: HtmlTemplateElement = doc.create_element("template").unwrap_throw().unchecked_into();
-        el.set_inner_html(&self.template_str);
-        el.content()
+    /// The template split into literal text and keys, in order
+    ///
+    /// Empty literals are skipped, so two keys next to each other come out as
+    /// two consecutive [`Segment::Key`]s.
+    pub fn segments(&self) -> Segments<'_> {
+        Segments {
+            template: self,
+            index: 0,
+            cursor: 0,
+        }
     }
 
-    #[cfg(feature = "wasm")]
-    pub fn render_elem<V: AsRef<str>>(&self, doc:&Document, data:&HashMap<&str, V>) -> Result<HtmlElement, Errors> {
-        self.render_fragment(doc, data)
-            .map(|frag| {
-                frag.first_child().unwrap().unchecked_into()
-            })
+    /// Substitutes only the keys that are in `vars`, and keeps the rest as
+    /// placeholders in the returned template
+    ///
+    /// Keys in `vars` that the template doesn't use are ignored. Rendering the
+    /// result with the remaining keys gives the same output as rendering the
+    /// original with all of them.
+    pub fn partial_render<V: ContextValue>(&self, vars:&HashMap<&str, V>) -> Template<'a> {
+        let template_str = &self.template_str;
+        let mut output = String::with_capacity(template_str.len());
+        let mut kept = Vec::new();
+
+        let values:Vec<Option<Cow<str>>> = self.keys.iter().map(|k| vars.get(k.as_ref()).map(|v| v.resolve())).collect();
+
+        // The marks move along with the text around them
+        let mut marks = self.marks.iter().peekable();
+        let mut moved = Vec::with_capacity(self.marks.len());
+
+        let mut cursor:usize = 0;
+
+        for (slot, (start, end)) in self.replaces.iter() {
+            while let Some((position, mark)) = marks.next_if(|(position, _)| position <= start) {
+                moved.push((output.len() + position - cursor, mark.clone()));
+            }
+            output.push_str(&template_str[cursor..*start]);
+            match &values[*slot] {
+                Some(v) => output.push_str(v),
+                None => {
+                    let new_start = output.len();
+                    output.push_str(&template_str[*start..*end]);
+                    kept.push((self.keys[*slot].clone(), new_start, output.len()));
+                }
+            }
+            cursor = *end;
+        }
+
+        for (position, mark) in marks {
+            moved.push((output.len() + position - cursor, mark.clone()));
+        }
+        if cursor < template_str.len() {
+            output.push_str(&template_str[cursor..]);
+        }
+
+        let mut template = Template::empty(Cow::Owned(output));
+        template.source_hash = precompiled::source_hash(&template.template_str, Whitespace::Preserve);
+        for (key, start, end) in kept {
+            template.push_replace(key, start, end);
+        }
+
+        // Lists aren't filled in until a DOM render
+        for (_, mark) in moved.iter() {
+            if let Mark::Each { items, .. } = mark {
+                template.push_key(items.clone());
+            }
+        }
+        template.marks = moved;
+        template
     }
 
-    #[cfg(feature = "wasm")]
-    pub fn render_elem_plain(&self, doc:&Document) -> HtmlElement {
-        let frag = self.render_fragment_plain(doc);
-        frag.first_child().unwrap_throw().unchecked_into()
+    /// Copies whatever is borrowed, so the template can outlive its input,
+    /// e.g. to parse a template from a `String` and keep it around
+    pub fn into_owned(self) -> Template<'static> {
+        let keys:Vec<Cow<'static, str>> = self.keys.into_iter().map(|k| Cow::Owned(k.into_owned())).collect();
+        let slots = keys.iter().cloned().enumerate().map(|(slot, key)| (key, slot)).collect();
+
+        Template {
+            keys,
+            slots,
+            replaces: self.replaces,
+            template_str: Cow::Owned(self.template_str.into_owned()),
+            marks: self.marks.into_iter().map(|(position, mark)| (position, mark.into_owned())).collect(),
+            source_hash: self.source_hash,
+        }
     }
+
 }
 
 
-/// render functions panic if the template name doesn't exist
+// The text for each slot, given what `Template::find` found. This is where
+// lazy values are computed, so each one is computed at most once per render,
+// and only if its key is used.
+fn evaluate<'v, V: ContextValue>(found:&[Option<&'v V>], values:&mut Vec<Option<Cow<'v, str>>>) {
+    values.clear();
+    values.extend(found.iter().map(|v| v.map(|v| v.resolve())));
+}
+
+/// The string render functions panic if the template name doesn't exist, the
+/// DOM ones return an [`Error::UnknownTemplate`]
+///
+/// Names can be namespaced with `::`, e.g. `admin::header`, see
+/// [`Self::mount`](TemplateCache::mount).
+///
+/// The render functions use [`Self::options`](TemplateCache::options), which
+/// defaults to [`RenderOptions::strict`]. The `_with` variants override it for
+/// a single call.
 pub struct TemplateCache <'a> {
-    pub templates: HashMap<&'a str, Template<'a>>,
+    pub templates: HashMap<Cow<'a, str>, Template<'a>>,
+    pub options: RenderOptions,
     #[cfg(feature = "wasm")]
     pub doc: Document,
+    // Renders to `doc`, keeping each template parsed after first use
+    #[cfg(feature = "wasm")]
+    dom: DomRenderer<WebDom>,
 }
 
 impl <'a> TemplateCache <'a> {
 
+    /// Panics if a template fails to parse. If a name is used twice, the
+    /// last template with that name is kept.
     pub fn new(templates:&[(&'a str, &'a str)]) -> Self{
-        let mut _templates = HashMap::new();
+        let mut cache = Self::_new(HashMap::new());
+
+        for (name, data) in templates {
+            cache.templates.insert(Cow::Borrowed(*name), Template::new(data).unwrap());
+        }
+
+        cache
+    }
+
+    /// Same as [`Self::new`], but returns the first template that fails to
+    /// parse, and treats a name that's used twice as an
+    /// [`Error::DuplicateTemplate`]
+    pub fn try_new(templates:&[(&'a str, &'a str)]) -> Result<Self, Error> {
+        let mut cache = Self::_new(HashMap::new());
 
         for (name, data) in templates {
-            _templates.insert(*name, Template::new(data).unwrap());
+            cache.insert(*name, Template::new(data)?)?;
         }
 
-        Self::_new(_templates)
+        Ok(cache)
+    }
+
+    /// Sets the default options used by the render functions
+    pub fn with_options(mut self, options:RenderOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    pub fn set_options(&mut self, options:RenderOptions) {
+        self.options = options;
     }
 
     cfg_if::cfg_if! {
         if #[cfg(feature = "wasm")] {
-            fn _new(_templates:HashMap<&'a str, Template<'a>>) -> Self {
+            fn _new(_templates:HashMap<Cow<'a, str>, Template<'a>>) -> Self {
                 let window = web_sys::window().unwrap_throw();
                 let doc = window.document().unwrap_throw();
 
-                Self { templates: _templates, doc }
+                let dom = DomRenderer::new(WebDom::new(doc.clone()));
+
+                Self { templates: _templates, options: RenderOptions::default(), doc, dom }
             }
         } else {
-            fn _new(_templates:HashMap<&'a str, Template<'a>>) -> Self {
-                Self {templates: _templates }
+            fn _new(_templates:HashMap<Cow<'a, str>, Template<'a>>) -> Self {
+                Self {templates: _templates, options: RenderOptions::default() }
             }
         }
     }
 
-    pub fn render<V: AsRef<str>>(&self, name:&str, data:&HashMap<&str,V>) -> Result<String, Errors> {
-        self.templates.get(name).unwrap().render(data)
-    }
-
-    pub fn render_plain(&self, name:&str) -> &str {
-        self.templates.get(name).unwrap().template_str
-    }
-
-    #[cfg(feature = "wasm")]
-    pub fn render_fragment<V: AsRef<str>>(&self, name:&str, data:&HashMap<&str, V>) -> Result<DocumentFragment, Errors> {
-        self.templates.get(name).unwrap_throw().render_fragment(&self.doc, data)
+    pub fn render<V: ContextValue>(&self, name:&str, data:&HashMap<&str,V>) -> Result<String, Errors> {
+        self.render_with(name, data, &self.options)
     }
 
-    #[cfg(feature = "wasm")]
-    pub fn render_fragment_plain(&self, name:&str) -> DocumentFragment {
-        self.templates.get(name).unwrap_throw().render_fragment_plain(&self.doc)
+    pub fn render_with<V: ContextValue>(&self, name:&str, data:&HashMap<&str,V>, options:&RenderOptions) -> Result<String, Errors> {
+        self.templates.get(name).unwrap().render_with(data, options)
     }
 
-    #[cfg(feature = "wasm")]
-    pub fn render_elem<V: AsRef<str>>(&self, name:&str, data:&HashMap<&str, V>) -> Result<HtmlElement, Errors> {
-        self.templates.get(name).unwrap_throw().render_elem(&self.doc, data)
-    }
-
-    #[cfg(feature = "wasm")]
-    pub fn render_elem_plain(&self, name:&str) -> HtmlElement {
-        self.templates.get(name).unwrap_throw().render_elem_plain(&self.doc)
+    pub fn render_plain(&self, name:&str) -> &str {
+        &self.templates.get(name).unwrap().template_str
     }
 }
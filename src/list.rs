@@ -0,0 +1,280 @@
+use crate::backend::DomBackend;
+use crate::bind::{self, ListBinding, Rendered};
+use crate::instance::{self, RowHandlers, Schema};
+use crate::{ContextValue, Error, Errors, Handlers, RenderOptions, TemplateInstance, Value};
+use std::collections::{HashMap, HashSet};
+
+/// Rows rendered from one template into the children of an element, which
+/// are kept in step with a list of items
+///
+/// Lists come from `${#each items template=row key=field}` in an element's
+/// start tag. The items are the [`Value::List`] under `items` in the values
+/// the instance is rendered or updated with, and each one is rendered with
+/// the template called `row` (by default, the one called `items`), found
+/// relative to the template's namespace, into a [`TemplateInstance`]. The
+/// rows go after anything that's already in the element. See
+/// [`TemplateInstance::list`].
+///
+/// When the items change, rows are matched up by the value of their `key`
+/// field, or by position without one, so a row whose item is still there is
+/// updated in place (and keeps its focus, scroll position, etc.), rather than
+/// rendered again. Rows are only moved when they have to be: of the rows that
+/// stay, the longest run that's already in the right order is left where it
+/// is.
+pub struct KeyedList<B: DomBackend> {
+    parent: B::Node,
+    items: String,
+    template: String,
+    key: Option<String>,
+    rows: Vec<Row<B>>,
+    // The templates that rows can be rendered with
+    schema: Schema<B>,
+    // Makes the handlers for each new row, see `Handlers::rows`
+    handlers: Option<RowHandlers<B>>,
+}
+
+struct Row<B: DomBackend> {
+    key: String,
+    instance: TemplateInstance<B>,
+}
+
+// What an update does to a list, worked out before anything is changed
+pub(crate) struct Prepared<'v, B: DomBackend> {
+    keys: Vec<String>,
+    plans: Vec<Plan<'v, B>>,
+}
+
+// What to do with each item
+enum Plan<'v, B: DomBackend> {
+    // Update the row that was at this index
+    Keep(usize, instance::Prepared<'v, B>),
+    New(Box<TemplateInstance<B>>),
+}
+
+impl <B: DomBackend> KeyedList<B> {
+    // An empty list in `parent`, whose rows are rendered with the template
+    // called `template`, which is the full name of the list's template
+    pub(crate) fn new(parent:B::Node, list:&ListBinding, template:String, schema:Schema<B>, handlers:Option<RowHandlers<B>>) -> Self {
+        Self {
+            parent,
+            items: list.items.clone(),
+            template,
+            key: list.key.clone(),
+            rows: Vec::new(),
+            schema,
+            handlers,
+        }
+    }
+
+    /// The key of the items in the values
+    pub fn items(&self) -> &str {
+        &self.items
+    }
+
+    /// The full name of the template the rows are rendered with, see
+    /// [`TemplateCache::resolve`](crate::TemplateCache::resolve)
+    pub fn template(&self) -> &str {
+        &self.template
+    }
+
+    pub fn len(&self) -> usize {
+        self.rows.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+
+    /// The row for `key`, which is the row's position as a string if the
+    /// list isn't keyed
+    pub fn get(&self, key:&str) -> Option<&TemplateInstance<B>> {
+        self.rows.iter().find(|row| row.key == key).map(|row| &row.instance)
+    }
+
+    /// The keys of the rows, in order
+    pub fn keys(&self) -> impl Iterator<Item = &str> {
+        self.rows.iter().map(|row| row.key.as_str())
+    }
+}
+
+impl <B: DomBackend + Clone> KeyedList<B> {
+    // Works out how to make the rows match the items in `data`, rendering the
+    // new rows, without changing the list or its element
+    pub(crate) fn prepare<'v, V: ContextValue>(&mut self, dom:&B, data:&'v HashMap<&str, V>, options:&RenderOptions) -> Result<Prepared<'v, B>, Errors> {
+        let error = |e| Errors { inner: vec![e] };
+        let items = items(data, &self.items).map_err(error)?;
+        let keys = keys_of(items, self.key.as_deref()).map_err(error)?;
+
+        let old:HashMap<&str, usize> = self.rows.iter().enumerate().map(|(i, row)| (row.key.as_str(), i)).collect();
+        let found:Vec<Option<usize>> = keys.iter().map(|key| old.get(key.as_str()).copied()).collect();
+
+        let mut plans = Vec::with_capacity(items.len());
+        let mut errors = Vec::new();
+        for ((key, item), found) in keys.iter().zip(items.iter()).zip(found) {
+            let plan = match found {
+                Some(i) => self.rows[i].instance.prepare(item, options).map(|prepared| Plan::Keep(i, prepared)),
+                None => {
+                    let handlers = self.handlers.as_mut().map_or_else(Handlers::new, |f| f(key));
+                    TemplateInstance::new(dom, &self.schema, &self.template, item, handlers, options).map(|instance| Plan::New(Box::new(instance)))
+                },
+            };
+            match plan {
+                Ok(plan) => plans.push(plan),
+                Err(e) => errors.extend(e.inner),
+            }
+        }
+        if !errors.is_empty() {
+            return Err(Errors { inner: errors });
+        }
+
+        Ok(Prepared { keys, plans })
+    }
+
+    // Picks up the rows of an empty list from a render for hydration, which
+    // are the nodes after each row marker in the element, for the items in
+    // `data`
+    pub(crate) fn hydrate<V: ContextValue>(&mut self, dom:&B, data:&HashMap<&str, V>, options:&RenderOptions) -> Result<(), Errors> {
+        let error = |e| Errors { inner: vec![e] };
+        let items = items(data, &self.items).map_err(error)?;
+        let keys = keys_of(items, self.key.as_deref()).map_err(error)?;
+
+        let children = dom.child_nodes(&self.parent);
+        let markers:Vec<usize> = (0..children.len()).filter(|i| bind::is_row_marker(dom, &children[*i])).collect();
+        if markers.len() != items.len() {
+            return Err(error(Error::Hydration(format!("{} rows in the list \"{}\", but {} items", markers.len(), self.items, items.len()))));
+        }
+
+        // Going backwards, so any text that hydrating a row adds doesn't
+        // shift the rows that are left. The handlers are still made in order.
+        let mut rows = Vec::with_capacity(items.len());
+        let mut errors = Vec::new();
+        let mut end = children.len();
+        let handlers:Vec<Handlers<B>> = keys.iter().map(|key| self.handlers.as_mut().map_or_else(Handlers::new, |f| f(key))).collect();
+        for (((key, item), handlers), start) in keys.into_iter().zip(items.iter()).zip(handlers).zip(markers).rev() {
+            let rendered = Rendered {
+                parent: &self.parent,
+                nodes: children[(start + 1)..end].to_vec(),
+                next: children.get(end),
+            };
+            match TemplateInstance::hydrate(dom, &self.schema, &self.template, rendered, item, handlers, options) {
+                Ok(instance) => rows.push(Row { key, instance }),
+                Err(e) => errors.extend(e.inner),
+            }
+            dom.remove(&children[start]);
+            end = start;
+        }
+        if !errors.is_empty() {
+            return Err(Errors { inner: errors });
+        }
+
+        rows.reverse();
+        self.rows = rows;
+        Ok(())
+    }
+
+    pub(crate) fn apply(&mut self, dom:&B, prepared:Prepared<B>) {
+        // The rows that stay, by their old position
+        let kept:Vec<usize> = prepared.plans.iter().filter_map(|plan| match plan {
+            Plan::Keep(i, _) => Some(*i),
+            Plan::New(_) => None,
+        }).collect();
+        let mut in_place = longest_increasing(&kept).into_iter();
+
+        let mut old_rows:Vec<Option<Row<B>>> = self.rows.drain(..).map(Some).collect();
+        let mut rows = Vec::with_capacity(prepared.plans.len());
+        for (key, plan) in prepared.keys.into_iter().zip(prepared.plans) {
+            let (instance, moved) = match plan {
+                Plan::Keep(i, prepared) => {
+                    let mut instance = old_rows[i].take().unwrap().instance;
+                    instance.apply(prepared);
+                    (instance, !in_place.next().unwrap())
+                },
+                Plan::New(instance) => (*instance, true),
+            };
+            rows.push((Row { key, instance }, moved));
+        }
+
+        for row in old_rows.into_iter().flatten() {
+            for node in row.instance.nodes() {
+                dom.remove(node);
+            }
+        }
+
+        // Going backwards, so the row after each one is already in place
+        let mut next:Option<B::Node> = None;
+        for (row, moved) in rows.iter().rev() {
+            if *moved {
+                for node in row.instance.nodes() {
+                    dom.insert_before(&self.parent, node, next.as_ref());
+                }
+            }
+            if let Some(first) = row.instance.nodes().first() {
+                next = Some(first.clone());
+            }
+        }
+
+        self.rows = rows.into_iter().map(|(row, _)| row).collect();
+    }
+}
+
+// The items for the list under `key` in `data`. A missing list is empty, since
+// missing keys are checked along with the rest of the values.
+pub(crate) fn items<'v, V: ContextValue>(data:&'v HashMap<&str, V>, key:&str) -> Result<&'v [HashMap<&'v str, Value<'v>>], Error> {
+    match data.get(key) {
+        Some(value) => value.list().ok_or_else(|| Error::NotAList(key.to_string())),
+        None => Ok(&[]),
+    }
+}
+
+// The key of each item, which is its `field`, or its position if there isn't
+// one
+pub(crate) fn keys_of<V: ContextValue>(items:&[HashMap<&str, V>], field:Option<&str>) -> Result<Vec<String>, Error> {
+    let mut keys = Vec::with_capacity(items.len());
+    let mut seen = HashSet::new();
+
+    for (i, item) in items.iter().enumerate() {
+        let key = match field {
+            Some(field) => match item.get(field) {
+                Some(value) => value.resolve().into_owned(),
+                None => return Err(Error::Missing(field.to_string())),
+            },
+            None => i.to_string(),
+        };
+        if !seen.insert(key.clone()) {
+            return Err(Error::DuplicateKey(key));
+        }
+        keys.push(key);
+    }
+
+    Ok(keys)
+}
+
+// Whether each number in `seq` is part of a longest increasing subsequence
+//
+// This is the usual O(n log n) one: `tails[len]` is the index of the smallest
+// number that ends an increasing run of `len + 1`, and `prev` links each
+// number to the one before it in its run.
+pub(crate) fn longest_increasing(seq:&[usize]) -> Vec<bool> {
+    let mut tails:Vec<usize> = Vec::new();
+    let mut prev = vec![None; seq.len()];
+
+    for (i, n) in seq.iter().enumerate() {
+        let len = tails.partition_point(|&t| seq[t] < *n);
+        if len > 0 {
+            prev[i] = Some(tails[len - 1]);
+        }
+        if len == tails.len() {
+            tails.push(i);
+        } else {
+            tails[len] = i;
+        }
+    }
+
+    let mut keep = vec![false; seq.len()];
+    let mut i = tails.last().copied();
+    while let Some(j) = i {
+        keep[j] = true;
+        i = prev[j];
+    }
+    keep
+}
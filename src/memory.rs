@@ -0,0 +1,557 @@
+//! A small DOM that lives in memory, for rendering outside of a browser
+
+use crate::backend::{DomBackend, NodeKind};
+use crate::Handlers;
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::{Rc, Weak};
+
+// Elements that never have children or an end tag
+const VOID_ELEMENTS:&[&str] = &[
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "source", "track", "wbr",
+];
+
+// Elements whose content is text up to the end tag. Entities are decoded in
+// the escapable ones, but not in the others.
+const RAW_TEXT_ELEMENTS:&[&str] = &["script", "style"];
+const ESCAPABLE_RAW_TEXT_ELEMENTS:&[&str] = &["textarea", "title"];
+
+/// [`DomBackend`] that builds a tree of [`MemoryNode`]s
+///
+/// Its HTML parser is a lot simpler than a browser's: it doesn't fix up bad
+/// nesting, move elements out of tables, or close `<p>`s implicitly. End tags
+/// close the nearest open element with the same name, and ones that don't
+/// match anything are ignored. `/>` closes any element, not just SVG ones.
+///
+/// Event handlers are plain closures, which are called by
+/// [`MemoryNode::dispatch`].
+#[derive(Clone, Copy, Debug, Default)]
+pub struct MemoryDom;
+
+impl MemoryDom {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+/// A node made by [`MemoryDom`]
+///
+/// Cloning a `MemoryNode` gives another reference to the same node, like
+/// cloning a `web_sys::Node`. Use [`DomBackend::clone_node`] for a copy.
+#[derive(Clone)]
+pub struct MemoryNode(Rc<RefCell<NodeData>>);
+
+struct NodeData {
+    kind: Kind,
+    parent: Weak<RefCell<NodeData>>,
+    children: Vec<MemoryNode>,
+    listeners: Vec<(String, Handler)>,
+}
+
+type Handler = Rc<dyn Fn(&MemoryNode)>;
+
+enum Kind {
+    Fragment,
+    // Attributes are kept in the order they were set
+    Element { tag: String, attributes: Vec<(String, String)> },
+    Text(String),
+    Comment(String),
+    // e.g. `doctype html`
+    Doctype(String),
+}
+
+impl MemoryNode {
+    fn new(kind:Kind) -> Self {
+        Self(Rc::new(RefCell::new(NodeData {
+            kind,
+            parent: Weak::new(),
+            children: Vec::new(),
+            listeners: Vec::new(),
+        })))
+    }
+
+    /// The node as HTML, or its children for a fragment
+    pub fn to_html(&self) -> String {
+        let mut output = String::new();
+        match self.0.borrow().kind {
+            Kind::Fragment => self.write_children(&mut output),
+            _ => self.write(&mut output),
+        }
+        output
+    }
+
+    /// Whether `self` and `other` are the same node, rather than equal ones
+    pub fn same_node(&self, other:&MemoryNode) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+
+    pub fn parent(&self) -> Option<MemoryNode> {
+        self.0.borrow().parent.upgrade().map(MemoryNode)
+    }
+
+    /// The tag name of an element, in lowercase
+    pub fn tag(&self) -> Option<String> {
+        match &self.0.borrow().kind {
+            Kind::Element { tag, .. } => Some(tag.clone()),
+            _ => None,
+        }
+    }
+
+    // Whether setting this node's text to `text`, with `values` in it, lets
+    // the values end the comment or raw text element it's in when it's
+    // written out, since neither is escaped
+    pub(crate) fn breaks_out<'v>(&self, text:&str, mut values:impl Iterator<Item = &'v str>) -> bool {
+        match &self.0.borrow().kind {
+            Kind::Comment(_) => {
+                text.contains("-->")
+                    || text.contains("--!>")
+                    || values.any(|value| value.contains("--") || value.contains('>') || value.contains("<!"))
+            },
+            Kind::Text(_) => match self.parent().and_then(|parent| parent.tag()) {
+                Some(tag) if RAW_TEXT_ELEMENTS.contains(&tag.as_str()) => text.to_ascii_lowercase().contains(&format!("</{}", tag)),
+                _ => false,
+            },
+            _ => false,
+        }
+    }
+
+    /// Calls the listeners for `event` on this node
+    ///
+    /// Events don't bubble up to the parents.
+    pub fn dispatch(&self, event:&str) {
+        // Not called while borrowed, since a handler can change the node
+        let handlers:Vec<_> = self.0.borrow().listeners
+            .iter()
+            .filter(|(name, _)| name == event)
+            .map(|(_, handler)| handler.clone())
+            .collect();
+
+        for handler in handlers {
+            handler(self);
+        }
+    }
+
+    fn append(&self, child:MemoryNode) {
+        child.0.borrow_mut().parent = Rc::downgrade(&self.0);
+        self.0.borrow_mut().children.push(child);
+    }
+
+    // Takes the node out of its parent, if it has one
+    fn detach(&self) {
+        if let Some(parent) = self.parent() {
+            parent.0.borrow_mut().children.retain(|child| !child.same_node(self));
+        }
+        self.0.borrow_mut().parent = Weak::new();
+    }
+
+    // Listeners aren't copied, same as `cloneNode` in a browser
+    fn deep_clone(&self) -> MemoryNode {
+        let data = self.0.borrow();
+        let kind = match &data.kind {
+            Kind::Fragment => Kind::Fragment,
+            Kind::Element { tag, attributes } => Kind::Element { tag: tag.clone(), attributes: attributes.clone() },
+            Kind::Text(text) => Kind::Text(text.clone()),
+            Kind::Comment(text) => Kind::Comment(text.clone()),
+            Kind::Doctype(text) => Kind::Doctype(text.clone()),
+        };
+
+        let copy = MemoryNode::new(kind);
+        for child in data.children.iter() {
+            copy.append(child.deep_clone());
+        }
+        copy
+    }
+
+    fn write(&self, output:&mut String) {
+        let data = self.0.borrow();
+        match &data.kind {
+            Kind::Fragment => self.write_children(output),
+            Kind::Element { tag, attributes } => {
+                output.push('<');
+                output.push_str(tag);
+                for (name, value) in attributes {
+                    output.push(' ');
+                    output.push_str(name);
+                    output.push_str("=\"");
+                    escape_into(value, &['&', '"'], output);
+                    output.push('"');
+                }
+                output.push('>');
+
+                if VOID_ELEMENTS.contains(&tag.as_str()) {
+                    return;
+                }
+
+                if RAW_TEXT_ELEMENTS.contains(&tag.as_str()) {
+                    for child in data.children.iter() {
+                        if let Kind::Text(text) = &child.0.borrow().kind {
+                            output.push_str(text);
+                        }
+                    }
+                } else {
+                    self.write_children(output);
+                }
+
+                output.push_str("</");
+                output.push_str(tag);
+                output.push('>');
+            },
+            Kind::Text(text) => escape_into(text, &['&', '<', '>'], output),
+            Kind::Comment(text) => {
+                output.push_str("<!--");
+                output.push_str(text);
+                output.push_str("-->");
+            },
+            Kind::Doctype(text) => {
+                output.push_str("<!");
+                output.push_str(text);
+                output.push('>');
+            },
+        }
+    }
+
+    fn write_children(&self, output:&mut String) {
+        for child in self.0.borrow().children.iter() {
+            child.write(output);
+        }
+    }
+}
+
+impl fmt::Debug for MemoryNode {
+    fn fmt(&self, f:&mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("MemoryNode").field(&self.to_html()).finish()
+    }
+}
+
+impl DomBackend for MemoryDom {
+    type Node = MemoryNode;
+    type Handler = Handler;
+
+    fn parse_fragment(&self, html:&str) -> MemoryNode {
+        parse(html)
+    }
+
+    fn clone_node(&self, node:&MemoryNode) -> MemoryNode {
+        node.deep_clone()
+    }
+
+    fn kind(&self, node:&MemoryNode) -> NodeKind {
+        match node.0.borrow().kind {
+            Kind::Fragment => NodeKind::Fragment,
+            Kind::Element { .. } => NodeKind::Element,
+            Kind::Text(_) => NodeKind::Text,
+            Kind::Comment(_) => NodeKind::Comment,
+            Kind::Doctype(_) => NodeKind::Other,
+        }
+    }
+
+    fn create_text(&self, text:&str) -> MemoryNode {
+        MemoryNode::new(Kind::Text(text.to_string()))
+    }
+
+    fn child_nodes(&self, node:&MemoryNode) -> Vec<MemoryNode> {
+        node.0.borrow().children.clone()
+    }
+
+    fn child(&self, node:&MemoryNode, index:usize) -> Option<MemoryNode> {
+        node.0.borrow().children.get(index).cloned()
+    }
+
+    fn insert_before(&self, parent:&MemoryNode, node:&MemoryNode, reference:Option<&MemoryNode>) {
+        node.detach();
+
+        let mut data = parent.0.borrow_mut();
+        let index = reference
+            .and_then(|reference| data.children.iter().position(|child| child.same_node(reference)))
+            .unwrap_or(data.children.len());
+        data.children.insert(index, node.clone());
+        node.0.borrow_mut().parent = Rc::downgrade(&parent.0);
+    }
+
+    fn remove(&self, node:&MemoryNode) {
+        node.detach();
+    }
+
+    fn attributes(&self, node:&MemoryNode) -> Vec<(String, String)> {
+        match &node.0.borrow().kind {
+            Kind::Element { attributes, .. } => attributes.clone(),
+            _ => Vec::new(),
+        }
+    }
+
+    fn get_attribute(&self, node:&MemoryNode, name:&str) -> Option<String> {
+        match &node.0.borrow().kind {
+            Kind::Element { attributes, .. } => attributes.iter().find(|(n, _)| n == name).map(|(_, value)| value.clone()),
+            _ => None,
+        }
+    }
+
+    fn set_attribute(&self, node:&MemoryNode, name:&str, value:&str) {
+        if let Kind::Element { attributes, .. } = &mut node.0.borrow_mut().kind {
+            match attributes.iter_mut().find(|(n, _)| n == name) {
+                Some((_, old)) => *old = value.to_string(),
+                None => attributes.push((name.to_string(), value.to_string())),
+            }
+        }
+    }
+
+    fn remove_attribute(&self, node:&MemoryNode, name:&str) {
+        if let Kind::Element { attributes, .. } = &mut node.0.borrow_mut().kind {
+            attributes.retain(|(n, _)| n != name);
+        }
+    }
+
+    fn text(&self, node:&MemoryNode) -> String {
+        match &node.0.borrow().kind {
+            Kind::Text(text) | Kind::Comment(text) => text.clone(),
+            _ => String::new(),
+        }
+    }
+
+    fn set_text(&self, node:&MemoryNode, text:&str) {
+        match &mut node.0.borrow_mut().kind {
+            Kind::Text(old) | Kind::Comment(old) => *old = text.to_string(),
+            _ => {},
+        }
+    }
+
+    fn add_listener(&self, node:&MemoryNode, event:&str, handler:&Self::Handler) {
+        node.0.borrow_mut().listeners.push((event.to_string(), handler.clone()));
+    }
+
+    fn remove_listener(&self, node:&MemoryNode, event:&str, handler:&Self::Handler) {
+        node.0.borrow_mut().listeners.retain(|(name, other)| !(name == event && Rc::ptr_eq(other, handler)));
+    }
+}
+
+impl Handlers<MemoryDom> {
+    /// Adds `f` as the handler for `key`
+    pub fn on(mut self, key:impl Into<String>, f:impl Fn(&MemoryNode) + 'static) -> Self {
+        self.insert(key, Rc::new(f));
+        self
+    }
+}
+
+fn parse(html:&str) -> MemoryNode {
+    let root = MemoryNode::new(Kind::Fragment);
+    // The open elements, innermost last
+    let mut open = vec![root.clone()];
+    let mut text = String::new();
+    let mut rest = html;
+
+    while !rest.is_empty() {
+        let parent = open.last().unwrap().clone();
+
+        // Anything that isn't markup is text, including a `<` that doesn't
+        // start a tag
+        if !starts_markup(rest) {
+            let first = rest.chars().next().unwrap().len_utf8();
+            let end = rest[first..].find('<').map(|i| i + first).unwrap_or(rest.len());
+            text.push_str(&rest[..end]);
+            rest = &rest[end..];
+            continue;
+        }
+        flush_text(&parent, &mut text);
+
+        if let Some(after) = rest.strip_prefix("<!--") {
+            let (comment, after) = split_at_str(after, "-->");
+            parent.append(MemoryNode::new(Kind::Comment(comment.to_string())));
+            rest = after;
+        } else if rest.starts_with("<!") || rest.starts_with("<?") {
+            let (inner, after) = split_at_str(&rest[2..], ">");
+            let kind = match rest.as_bytes()[1] {
+                b'!' if inner.len() >= 7 && inner[..7].eq_ignore_ascii_case("doctype") => Kind::Doctype(inner.to_string()),
+                _ => Kind::Comment(inner.to_string()),
+            };
+            parent.append(MemoryNode::new(kind));
+            rest = after;
+        } else if let Some(after) = rest.strip_prefix("</") {
+            let (inner, after) = split_at_str(after, ">");
+            let tag = inner.trim().to_ascii_lowercase();
+            // The fragment at the bottom never matches, since it has no tag
+            if let Some(i) = open.iter().rposition(|node| node.tag().as_deref() == Some(tag.as_str())) {
+                open.truncate(i);
+            }
+            rest = after;
+        } else {
+            let (tag, attributes, self_closing, after) = parse_start_tag(&rest[1..]);
+            let element = MemoryNode::new(Kind::Element { tag: tag.clone(), attributes });
+            parent.append(element.clone());
+            rest = after;
+
+            let raw = RAW_TEXT_ELEMENTS.contains(&tag.as_str());
+            let escapable = ESCAPABLE_RAW_TEXT_ELEMENTS.contains(&tag.as_str());
+            if self_closing || VOID_ELEMENTS.contains(&tag.as_str()) {
+                // no children
+            } else if raw || escapable {
+                let end = find_end_tag(rest, &tag);
+                if end > 0 {
+                    let content = &rest[..end];
+                    let content = if raw { content.to_string() } else { decode(content) };
+                    element.append(MemoryNode::new(Kind::Text(content)));
+                }
+                let (_, after) = split_at_str(&rest[end..], ">");
+                rest = after;
+            } else {
+                open.push(element);
+            }
+        }
+    }
+
+    flush_text(open.last().unwrap(), &mut text);
+    root
+}
+
+// Whether `s` starts with a tag, comment or doctype
+fn starts_markup(s:&str) -> bool {
+    let mut chars = s.chars();
+    if chars.next() != Some('<') {
+        return false;
+    }
+    match chars.next() {
+        Some('!') | Some('?') => true,
+        Some('/') => matches!(chars.next(), Some(c) if c.is_ascii_alphabetic()),
+        Some(c) => c.is_ascii_alphabetic(),
+        None => false,
+    }
+}
+
+fn flush_text(parent:&MemoryNode, text:&mut String) {
+    if !text.is_empty() {
+        parent.append(MemoryNode::new(Kind::Text(decode(text))));
+        text.clear();
+    }
+}
+
+// The part of `s` before `pattern`, and the part after it. Without a
+// `pattern`, everything is before it.
+fn split_at_str<'s>(s:&'s str, pattern:&str) -> (&'s str, &'s str) {
+    match s.find(pattern) {
+        Some(i) => (&s[..i], &s[i + pattern.len()..]),
+        None => (s, ""),
+    }
+}
+
+// Where `</tag` starts in `s`, or the end of `s` if it doesn't
+fn find_end_tag(s:&str, tag:&str) -> usize {
+    let lower = s.to_ascii_lowercase();
+    let pattern = format!("</{}", tag);
+    lower.find(&pattern).unwrap_or(s.len())
+}
+
+// Parses the rest of a start tag, after the `<`. Returns the tag name, the
+// attributes, whether it ended with `/>`, and what's left after the `>`.
+fn parse_start_tag(s:&str) -> (String, Vec<(String, String)>, bool, &str) {
+    let end = s.find(|c:char| c.is_ascii_whitespace() || c == '/' || c == '>').unwrap_or(s.len());
+    let tag = s[..end].to_ascii_lowercase();
+    let mut rest = &s[end..];
+    let mut attributes:Vec<(String, String)> = Vec::new();
+
+    loop {
+        rest = rest.trim_start();
+        if let Some(after) = rest.strip_prefix("/>") {
+            return (tag, attributes, true, after);
+        }
+        if let Some(after) = rest.strip_prefix('>') {
+            return (tag, attributes, false, after);
+        }
+        if rest.is_empty() {
+            return (tag, attributes, false, rest);
+        }
+        if let Some(after) = rest.strip_prefix('/') {
+            rest = after;
+            continue;
+        }
+
+        let end = rest
+            .find(|c:char| c.is_ascii_whitespace() || c == '=' || c == '>' || c == '/')
+            .unwrap_or(rest.len())
+            // an attribute name can start with `=` or `/`
+            .max(1);
+        let name = rest[..end].to_ascii_lowercase();
+        rest = rest[end..].trim_start();
+
+        let mut value = String::new();
+        if let Some(after) = rest.strip_prefix('=') {
+            rest = after.trim_start();
+            match rest.chars().next() {
+                Some(quote) if quote == '"' || quote == '\'' => {
+                    let (quoted, after) = split_at_str(&rest[1..], if quote == '"' { "\"" } else { "'" });
+                    value = decode(quoted);
+                    rest = after;
+                },
+                _ => {
+                    let end = rest.find(|c:char| c.is_ascii_whitespace() || c == '>').unwrap_or(rest.len());
+                    value = decode(&rest[..end]);
+                    rest = &rest[end..];
+                }
+            }
+        }
+
+        // Like a browser, the first of several attributes with the same name
+        // wins
+        if attributes.iter().all(|(other, _)| *other != name) {
+            attributes.push((name, value));
+        }
+    }
+}
+
+// Decodes the common named entities and numeric ones, leaving anything else
+// as it is
+fn decode(s:&str) -> String {
+    if !s.contains('&') {
+        return s.to_string();
+    }
+
+    let mut output = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(i) = rest.find('&') {
+        output.push_str(&rest[..i]);
+        rest = &rest[i..];
+
+        let decoded = rest.find(';').and_then(|end| {
+            let c = match &rest[1..end] {
+                "amp" => '&',
+                "lt" => '<',
+                "gt" => '>',
+                "quot" => '"',
+                "apos" => '\'',
+                "nbsp" => '\u{a0}',
+                entity => {
+                    let number = entity.strip_prefix('#')?;
+                    let code = match number.strip_prefix('x').or_else(|| number.strip_prefix('X')) {
+                        Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+                        None => number.parse().ok()?,
+                    };
+                    std::char::from_u32(code)?
+                }
+            };
+            Some((c, end))
+        });
+
+        match decoded {
+            Some((c, end)) => {
+                output.push(c);
+                rest = &rest[end + 1..];
+            },
+            None => {
+                output.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    output.push_str(rest);
+    output
+}
+
+fn escape_into(s:&str, special:&[char], output:&mut String) {
+    for c in s.chars() {
+        match c {
+            '&' if special.contains(&c) => output.push_str("&amp;"),
+            '<' if special.contains(&c) => output.push_str("&lt;"),
+            '>' if special.contains(&c) => output.push_str("&gt;"),
+            '"' if special.contains(&c) => output.push_str("&quot;"),
+            c => output.push(c),
+        }
+    }
+}
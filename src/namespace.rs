@@ -0,0 +1,97 @@
+use crate::{Error, Template, TemplateCache};
+use std::borrow::Cow;
+
+/// Separates namespaces in template names, e.g. `admin::header`
+///
+/// A name that starts with the separator, e.g. `::header`, is absolute: it is
+/// never resolved relative to another template.
+pub const SEPARATOR: &str = "::";
+
+impl <'a> TemplateCache <'a> {
+    /// Adds a template, failing if the name is already taken
+    pub fn insert(&mut self, name:impl Into<Cow<'a, str>>, template:Template<'a>) -> Result<(), Error> {
+        let name = name.into();
+        if self.templates.contains_key(&name) {
+            return Err(Error::DuplicateTemplate(name.into_owned()));
+        }
+        self.templates.insert(name, template);
+        Ok(())
+    }
+
+    pub fn remove(&mut self, name:&str) -> Option<Template<'a>> {
+        #[cfg(feature = "wasm")]
+        self.dom.forget(name);
+
+        self.templates.remove(name)
+    }
+
+    pub fn get(&self, name:&str) -> Option<&Template<'a>> {
+        self.templates.get(name)
+    }
+
+    /// Moves every template from `other` into this cache under `prefix`, so
+    /// `header` becomes `prefix::header`
+    ///
+    /// An empty prefix adds the templates under their own names. If any of
+    /// the new names are already taken, nothing is moved and the first
+    /// collision is returned. The default options of `other` are dropped.
+    pub fn mount(&mut self, prefix:&str, other:TemplateCache<'a>) -> Result<(), Error> {
+        let prefix = prefix.trim_start_matches(SEPARATOR);
+        let full_name = |name:&str| match prefix {
+            "" => name.to_string(),
+            _ => format!("{}{}{}", prefix, SEPARATOR, name),
+        };
+
+        // Sorted so that which collision gets reported doesn't depend on
+        // hashmap order
+        let mut names:Vec<&Cow<str>> = other.templates.keys().collect();
+        names.sort();
+        if let Some(name) = names.into_iter().map(|name| full_name(name)).find(|name| self.templates.contains_key(name.as_str())) {
+            return Err(Error::DuplicateTemplate(name));
+        }
+
+        for (name, template) in other.templates {
+            self.templates.insert(Cow::Owned(full_name(&name)), template);
+        }
+
+        Ok(())
+    }
+
+    /// Finds the template that `name` refers to when used from inside the
+    /// template called `from`, returning its full name
+    ///
+    /// This is how the templates for the rows of `${#each}` lists are found.
+    /// A relative name is looked up in the namespace of `from` first, and then
+    /// in each enclosing namespace up to the root. So from `admin::users::list`,
+    /// `header` tries `admin::users::header`, `admin::header` and `header`, in
+    /// that order. An absolute name like `::header` is only looked up at the
+    /// root.
+    pub fn resolve(&self, name:&str, from:&str) -> Option<&str> {
+        if let Some(absolute) = name.strip_prefix(SEPARATOR) {
+            return self.templates.get_key_value(absolute).map(|(k, _)| k.as_ref());
+        }
+
+        let mut namespace = namespace_of(from);
+        loop {
+            let candidate = match namespace {
+                Some(ns) => Cow::Owned(format!("{}{}{}", ns, SEPARATOR, name)),
+                None => Cow::Borrowed(name),
+            };
+
+            if let Some((k, _)) = self.templates.get_key_value(candidate.as_ref()) {
+                return Some(k.as_ref());
+            }
+
+            namespace = match namespace {
+                Some(ns) => namespace_of(ns),
+                None => return None,
+            };
+        }
+    }
+}
+
+// `a::b::c` => `a::b`, `c` => None
+fn namespace_of(name:&str) -> Option<&str> {
+    let name = name.trim_start_matches(SEPARATOR);
+    name.rfind(SEPARATOR).map(|i| &name[..i])
+}
@@ -0,0 +1,94 @@
+use std::fmt;
+use std::sync::Arc;
+
+/// What to do with key-value pairs that are never used by the template
+#[derive(Clone)]
+pub enum ExtraPolicy {
+    /// Silently skip unused keys
+    Ignore,
+    /// Fail the render with an [`Error::Extra`](crate::Error::Extra) per unused key
+    Fail,
+    /// Render anyway, but call the given function with each unused key
+    Warn(Arc<dyn Fn(&str) + Send + Sync>),
+}
+
+impl ExtraPolicy {
+    /// Convenience for building a [`ExtraPolicy::Warn`] out of a closure
+    pub fn warn(f: impl Fn(&str) + Send + Sync + 'static) -> Self {
+        Self::Warn(Arc::new(f))
+    }
+}
+
+impl fmt::Debug for ExtraPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Ignore => write!(f, "Ignore"),
+            Self::Fail => write!(f, "Fail"),
+            Self::Warn(_) => write!(f, "Warn(..)"),
+        }
+    }
+}
+
+/// What to do with keys in the template that have no value
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MissingPolicy {
+    /// Fail the render with an [`Error::Missing`](crate::Error::Missing) per missing key
+    Fail,
+    /// Replace the placeholder with nothing
+    Empty,
+    /// Leave the placeholder in the output as it was written, e.g. `${name}`
+    Marker,
+}
+
+/// Controls how strict [`Template::render_with`](crate::Template::render_with) is
+///
+/// The default is the same as [`Template::render`](crate::Template::render):
+/// both extra and missing keys are errors.
+#[derive(Clone, Debug)]
+pub struct RenderOptions {
+    pub extra: ExtraPolicy,
+    pub missing: MissingPolicy,
+}
+
+// For functions that need a reference to the default options that outlives
+// the call
+pub(crate) static STRICT: RenderOptions = RenderOptions {
+    extra: ExtraPolicy::Fail,
+    missing: MissingPolicy::Fail,
+};
+
+impl RenderOptions {
+    /// Both extra and missing keys are errors
+    pub fn strict() -> Self {
+        Self {
+            extra: ExtraPolicy::Fail,
+            missing: MissingPolicy::Fail,
+        }
+    }
+
+    /// Extra keys are ignored, missing keys are still errors
+    ///
+    /// Useful when one shared context map feeds several templates
+    pub fn lenient() -> Self {
+        Self {
+            extra: ExtraPolicy::Ignore,
+            missing: MissingPolicy::Fail,
+        }
+    }
+
+    pub fn with_extra(mut self, extra: ExtraPolicy) -> Self {
+        self.extra = extra;
+        self
+    }
+
+    pub fn with_missing(mut self, missing: MissingPolicy) -> Self {
+        self.missing = missing;
+        self
+    }
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        Self::strict()
+    }
+}
@@ -0,0 +1,351 @@
+//! A compact binary format for parsed templates
+//!
+//! This is meant for producing templates at build time (e.g. in a `build.rs`
+//! that writes [`Template::to_bytes`] to `OUT_DIR`) and loading them with
+//! `include_bytes!` at startup, without scanning the template text again.
+//! Loading borrows from the bytes, so nothing is copied either.
+//!
+//! The layout is little-endian throughout:
+//!
+//! ```text
+//! magic     "SHT" + b'T' for a template or b'C' for a cache
+//! version   u16, currently 2, which added the marks
+//!
+//! template  source hash u64, see [`Template::source_hash`]
+//!           template text (string)
+//!           key count u32, then each key (string)
+//!           placeholder count u32, then each (slot u32, start u32, end u32)
+//!           mark count u32, then each (position u32, kind u8, then the
+//!           fields for that kind)
+//!
+//! mark      b'R' for a `${ref name}`: name (string)
+//!           b'E' for a `${#each items template=row key=field}`: items
+//!           (string), which is also one of the keys, row (string), then
+//!           u8 1 and field (string) if there's a key, or u8 0 if not
+//!
+//! cache     template count u32, then each name (string) and template
+//!
+//! string    byte length u32, then utf-8 bytes
+//! ```
+
+use crate::{LoadError, Mark, Template, TemplateBuilder, TemplateCache, Whitespace};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::convert::TryInto;
+use std::str;
+
+const MAGIC: &[u8; 3] = b"SHT";
+const KIND_TEMPLATE: u8 = b'T';
+const KIND_CACHE: u8 = b'C';
+const VERSION: u16 = 2;
+const MARK_REF: u8 = b'R';
+const MARK_EACH: u8 = b'E';
+
+/// 64-bit FNV-1a of the source and the settings it's parsed with, which
+/// unlike `DefaultHasher` is stable across builds
+pub(crate) fn source_hash(source: &str, whitespace: Whitespace) -> u64 {
+    let setting = match whitespace {
+        Whitespace::Trim => 0,
+        Whitespace::Preserve => 1,
+        Whitespace::Collapse => 2,
+    };
+
+    source.bytes().chain(std::iter::once(setting)).fold(0xcbf2_9ce4_8422_2325, |hash, b| {
+        (hash ^ b as u64).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+impl <'a> Template <'a> {
+    /// Hash of the text this template was parsed from, along with the
+    /// [`TemplateBuilder`] settings it was parsed with
+    ///
+    /// For the result of [`Self::partial_render`], this is the hash of the
+    /// new template text, as if it was parsed with [`Whitespace::Preserve`].
+    pub fn source_hash(&self) -> u64 {
+        self.source_hash
+    }
+
+    /// Serializes the parsed template, see the [module docs](crate::precompiled)
+    /// for the format
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut w = Writer::new(KIND_TEMPLATE);
+        w.template(self);
+        w.bytes
+    }
+
+    /// Loads a template written by [`Self::to_bytes`]
+    ///
+    /// The template text and keys are borrowed from `bytes`.
+    pub fn from_bytes(bytes: &'a [u8]) -> Result<Self, LoadError> {
+        let mut r = Reader::new(bytes, KIND_TEMPLATE)?;
+        let template = r.template()?;
+        r.finish()?;
+        Ok(template)
+    }
+
+    /// Same as [`Self::from_bytes`], but also checks that the template was
+    /// compiled from `source` with the default settings, i.e. with
+    /// [`Template::new`]
+    ///
+    /// See [`TemplateBuilder::from_bytes_checked`] for other settings.
+    pub fn from_bytes_checked(bytes: &'a [u8], source: &str) -> Result<Self, LoadError> {
+        Template::builder(source).from_bytes_checked(bytes)
+    }
+}
+
+impl <'a> TemplateBuilder <'a> {
+    /// Loads a template written by [`Template::to_bytes`], checking that it
+    /// was compiled from this builder's input and settings
+    pub fn from_bytes_checked<'b>(&self, bytes: &'b [u8]) -> Result<Template<'b>, LoadError> {
+        let template = Template::from_bytes(bytes)?;
+        if template.source_hash != source_hash(self.input, self.whitespace) {
+            return Err(LoadError::SourceMismatch(None));
+        }
+        Ok(template)
+    }
+}
+
+impl <'a> TemplateCache <'a> {
+    /// Serializes every template in the cache, see the
+    /// [module docs](crate::precompiled) for the format
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut w = Writer::new(KIND_CACHE);
+
+        // Sorted so the output doesn't depend on hashmap order
+        let mut names:Vec<&Cow<str>> = self.templates.keys().collect();
+        names.sort();
+
+        w.u32(names.len());
+        for name in names {
+            w.str(name);
+            w.template(&self.templates[name]);
+        }
+        w.bytes
+    }
+
+    /// Loads a cache written by [`Self::to_bytes`]
+    ///
+    /// A name that's in there twice is a [`LoadError::DuplicateTemplate`].
+    pub fn from_bytes(bytes: &'a [u8]) -> Result<Self, LoadError> {
+        let mut r = Reader::new(bytes, KIND_CACHE)?;
+
+        let count = r.u32()?;
+        let mut templates = HashMap::new();
+        for _ in 0..count {
+            let name = r.str()?;
+            if templates.insert(Cow::Borrowed(name), r.template()?).is_some() {
+                return Err(LoadError::DuplicateTemplate(name.to_string()));
+            }
+        }
+        r.finish()?;
+
+        Ok(Self::_new(templates))
+    }
+
+    /// Same as [`Self::from_bytes`], but also checks that each template was
+    /// compiled from the matching source in `sources`, given as
+    /// `(name, source)` like in [`Self::new`], with the default settings
+    ///
+    /// Templates that aren't in `sources` aren't checked.
+    pub fn from_bytes_checked(bytes: &'a [u8], sources: &[(&str, &str)]) -> Result<Self, LoadError> {
+        let cache = Self::from_bytes(bytes)?;
+        for (name, source) in sources {
+            match cache.templates.get(*name) {
+                Some(template) if template.source_hash == source_hash(source, Whitespace::default()) => {},
+                _ => return Err(LoadError::SourceMismatch(Some(name.to_string()))),
+            }
+        }
+        Ok(cache)
+    }
+}
+
+struct Writer {
+    bytes: Vec<u8>,
+}
+
+impl Writer {
+    fn new(kind: u8) -> Self {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(MAGIC);
+        bytes.push(kind);
+        bytes.extend_from_slice(&VERSION.to_le_bytes());
+        Self { bytes }
+    }
+
+    fn u32(&mut self, n: usize) {
+        let n: u32 = n.try_into().expect("template too large to precompile");
+        self.bytes.extend_from_slice(&n.to_le_bytes());
+    }
+
+    fn str(&mut self, s: &str) {
+        self.u32(s.len());
+        self.bytes.extend_from_slice(s.as_bytes());
+    }
+
+    fn template(&mut self, template: &Template) {
+        self.bytes.extend_from_slice(&template.source_hash.to_le_bytes());
+        self.str(&template.template_str);
+
+        self.u32(template.keys.len());
+        for key in template.keys.iter() {
+            self.str(key);
+        }
+
+        self.u32(template.replaces.len());
+        for (slot, (start, end)) in template.replaces.iter() {
+            self.u32(*slot);
+            self.u32(*start);
+            self.u32(*end);
+        }
+
+        self.u32(template.marks.len());
+        for (position, mark) in template.marks.iter() {
+            self.u32(*position);
+            match mark {
+                Mark::Ref(name) => {
+                    self.bytes.push(MARK_REF);
+                    self.str(name);
+                },
+                Mark::Each { items, template, key } => {
+                    self.bytes.push(MARK_EACH);
+                    self.str(items);
+                    self.str(template);
+                    match key {
+                        Some(key) => {
+                            self.bytes.push(1);
+                            self.str(key);
+                        },
+                        None => self.bytes.push(0),
+                    }
+                },
+            }
+        }
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn new(bytes: &'a [u8], kind: u8) -> Result<Self, LoadError> {
+        if bytes.len() < 4 || &bytes[..3] != MAGIC || bytes[3] != kind {
+            return Err(LoadError::Magic);
+        }
+
+        let mut r = Self { bytes: &bytes[4..] };
+        let version = u16::from_le_bytes(r.take(2)?.try_into().unwrap());
+        if version != VERSION {
+            return Err(LoadError::Version(version));
+        }
+        Ok(r)
+    }
+
+    fn take(&mut self, n: usize) -> Result<&'a [u8], LoadError> {
+        if n > self.bytes.len() {
+            return Err(LoadError::Corrupt);
+        }
+        let (head, tail) = self.bytes.split_at(n);
+        self.bytes = tail;
+        Ok(head)
+    }
+
+    fn u32(&mut self) -> Result<usize, LoadError> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()) as usize)
+    }
+
+    fn u64(&mut self) -> Result<u64, LoadError> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    fn str(&mut self) -> Result<&'a str, LoadError> {
+        let len = self.u32()?;
+        str::from_utf8(self.take(len)?).map_err(|_| LoadError::Corrupt)
+    }
+
+    fn template(&mut self) -> Result<Template<'a>, LoadError> {
+        let hash = self.u64()?;
+        let template_str = self.str()?;
+
+        let mut template = Template::empty(Cow::Borrowed(template_str));
+        template.source_hash = hash;
+
+        let key_count = self.u32()?;
+        for slot in 0..key_count {
+            let key = self.str()?;
+            template.keys.push(Cow::Borrowed(key));
+            if template.slots.insert(Cow::Borrowed(key), slot).is_some() {
+                return Err(LoadError::Corrupt);
+            }
+        }
+
+        let replace_count = self.u32()?;
+        let mut cursor = 0;
+        for _ in 0..replace_count {
+            let slot = self.u32()?;
+            let start = self.u32()?;
+            let end = self.u32()?;
+
+            // Placeholders have to be in order, not overlap, and land on
+            // character boundaries, or rendering would panic
+            let valid = slot < key_count
+                && cursor <= start
+                && start <= end
+                && end <= template_str.len()
+                && template_str.is_char_boundary(start)
+                && template_str.is_char_boundary(end);
+            if !valid {
+                return Err(LoadError::Corrupt);
+            }
+
+            template.replaces.push((slot, (start, end)));
+            cursor = end;
+        }
+
+        let mark_count = self.u32()?;
+        let mut cursor = 0;
+        for _ in 0..mark_count {
+            let position = self.u32()?;
+            let mark = match self.take(1)?[0] {
+                MARK_REF => Mark::Ref(Cow::Borrowed(self.str()?)),
+                MARK_EACH => {
+                    let items = self.str()?;
+                    let row = self.str()?;
+                    let key = match self.take(1)?[0] {
+                        0 => None,
+                        1 => Some(Cow::Borrowed(self.str()?)),
+                        _ => return Err(LoadError::Corrupt),
+                    };
+
+                    // The items are one of the keys, see `TemplateBuilder::build`
+                    if !template.slots.contains_key(items) {
+                        return Err(LoadError::Corrupt);
+                    }
+                    Mark::Each { items: Cow::Borrowed(items), template: Cow::Borrowed(row), key }
+                },
+                _ => return Err(LoadError::Corrupt),
+            };
+
+            // Same as for the placeholders
+            let valid = cursor <= position
+                && position <= template_str.len()
+                && template_str.is_char_boundary(position);
+            if !valid {
+                return Err(LoadError::Corrupt);
+            }
+
+            template.marks.push((position, mark));
+            cursor = position;
+        }
+
+        Ok(template)
+    }
+
+    fn finish(self) -> Result<(), LoadError> {
+        if self.bytes.is_empty() {
+            Ok(())
+        } else {
+            Err(LoadError::Corrupt)
+        }
+    }
+}
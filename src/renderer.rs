@@ -0,0 +1,294 @@
+use crate::backend::DomBackend;
+use crate::bind::{self, Parsed, Refs};
+use crate::instance::{Schema, SchemaEntry};
+use crate::list;
+use crate::{ContextValue, Error, Errors, Handlers, Mark, RenderOptions, Template, TemplateCache, TemplateInstance, Value};
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+/// Renders the templates in a [`TemplateCache`] to DOM nodes
+///
+/// The `render_fragment`, `render_elem` and `render_elems` functions render
+/// the template to HTML the same way [`Template::render`] does, and parse
+/// that, so the values are put in as HTML (e.g. from `html_map!`).
+///
+/// The `render_fragment_text` functions skip the HTML parser instead. Each
+/// template is parsed into nodes the first time it's rendered, noting which
+/// text node or attribute each placeholder ended up in. After that, each
+/// render clones the parsed nodes and sets the values as the text of those
+/// nodes and attributes, so they should **not** be escaped beforehand, i.e.
+/// use `hash_map!` rather than `html_map!`. Placeholders that aren't in text,
+/// a comment, or an attribute value (e.g. `<div ${attrs}>`) can't be filled in
+/// this way, so those templates fail with an [`Error::Unbindable`]. The same
+/// goes for instances.
+///
+/// A `${#each items template=row}` gets a row rendered with the template
+/// called `row` for each item in the [`Value::List`] under `items`, in the same
+/// way as the template itself. `row` is found relative to the template's
+/// namespace, see [`TemplateCache::resolve`]. The plain renders leave the rows
+/// out.
+///
+/// A name that isn't in the cache is an [`Error::UnknownTemplate`], including
+/// the template for the rows of a list.
+///
+/// With the `wasm` feature, the cache has the same functions for the browser's
+/// DOM, which go through a `DomRenderer<WebDom>`.
+pub struct DomRenderer<B: DomBackend> {
+    backend: B,
+    parsed: RefCell<HashMap<String, Parsed<B>>>,
+}
+
+impl <B: DomBackend> DomRenderer<B> {
+    pub fn new(backend:B) -> Self {
+        Self {
+            backend,
+            parsed: RefCell::new(HashMap::new()),
+        }
+    }
+
+    pub fn backend(&self) -> &B {
+        &self.backend
+    }
+
+    pub fn render_fragment<V: ContextValue>(&self, cache:&TemplateCache, name:&str, data:&HashMap<&str, V>) -> Result<B::Node, Errors> {
+        self.render_fragment_with(cache, name, data, &cache.options)
+    }
+
+    pub fn render_fragment_with<V: ContextValue>(&self, cache:&TemplateCache, name:&str, data:&HashMap<&str, V>, options:&RenderOptions) -> Result<B::Node, Errors> {
+        self.render_fragment_refs_with(cache, name, data, options).map(|(frag, _)| frag)
+    }
+
+    pub fn render_fragment_plain(&self, cache:&TemplateCache, name:&str) -> Result<B::Node, Error> {
+        let template = find(cache, name)?;
+        let parsed = self.parsed(name, template);
+        let texts:Vec<Cow<str>> = parsed.template.keys.iter().map(|key| Cow::Owned(bind::marker(key))).collect();
+        Ok(match parsed.fill(&self.backend, &texts) {
+            Ok((frag, _)) => frag,
+            Err(_) => bind::parse(&self.backend, template, &texts).0,
+        })
+    }
+
+    /// Same as [`Self::render_fragment`], but also returns the elements that
+    /// were marked with `data-ref="name"` or `${ref name}`
+    ///
+    /// The `data-ref` attributes are removed from the fragment. All the DOM
+    /// render functions do that, these ones just also return the elements.
+    pub fn render_fragment_refs<V: ContextValue>(&self, cache:&TemplateCache, name:&str, data:&HashMap<&str, V>) -> Result<(B::Node, Refs<B::Node>), Errors> {
+        self.render_fragment_refs_with(cache, name, data, &cache.options)
+    }
+
+    pub fn render_fragment_refs_with<V: ContextValue>(&self, cache:&TemplateCache, name:&str, data:&HashMap<&str, V>, options:&RenderOptions) -> Result<(B::Node, Refs<B::Node>), Errors> {
+        let template = find(cache, name).map_err(|e| Errors { inner: vec![e] })?;
+        let texts = bind::texts(template, data, options, &bind::handler_only_slots(template))?;
+        let (frag, refs, lists) = bind::parse(&self.backend, template, &texts);
+
+        let lists = lists.into_iter().filter_map(|(i, node)| match &template.marks[i].1 {
+            Mark::Each { items, template, key } => Some((items.as_ref(), template.as_ref(), key.as_deref(), node)),
+            Mark::Ref(_) => None,
+        });
+        self.render_rows(cache, name, lists, data, |template, row| self.render_fragment_with(cache, template, row, options))?;
+        Ok((frag, refs))
+    }
+
+    /// Same as [`Self::render_fragment`], but the values are set as text on
+    /// clones of the parsed template, see [`DomRenderer`]
+    pub fn render_fragment_text<V: ContextValue>(&self, cache:&TemplateCache, name:&str, data:&HashMap<&str, V>) -> Result<B::Node, Errors> {
+        self.render_fragment_text_with(cache, name, data, &cache.options)
+    }
+
+    pub fn render_fragment_text_with<V: ContextValue>(&self, cache:&TemplateCache, name:&str, data:&HashMap<&str, V>, options:&RenderOptions) -> Result<B::Node, Errors> {
+        self.render_fragment_text_refs_with(cache, name, data, options).map(|(frag, _)| frag)
+    }
+
+    pub fn render_fragment_text_refs<V: ContextValue>(&self, cache:&TemplateCache, name:&str, data:&HashMap<&str, V>) -> Result<(B::Node, Refs<B::Node>), Errors> {
+        self.render_fragment_text_refs_with(cache, name, data, &cache.options)
+    }
+
+    pub fn render_fragment_text_refs_with<V: ContextValue>(&self, cache:&TemplateCache, name:&str, data:&HashMap<&str, V>, options:&RenderOptions) -> Result<(B::Node, Refs<B::Node>), Errors> {
+        let template = find(cache, name).map_err(|e| Errors { inner: vec![e] })?;
+        let parsed = self.parsed(name, template);
+        let bound = parsed.bound()?;
+        let texts = bind::texts(template, data, options, &parsed.handler_only_slots())?;
+        let (frag, refs) = parsed.fill(&self.backend, &texts)?;
+
+        let lists = bound.lists.iter().map(|list| (list.items.as_str(), list.template.as_str(), list.key.as_deref(), list.node(&self.backend, &frag)));
+        self.render_rows(cache, name, lists, data, |template, row| self.render_fragment_text_with(cache, template, row, options))?;
+        Ok((frag, refs))
+    }
+
+    // Appends a row rendered with `render` to each list's element for each of
+    // its items, going by the list's (items, template, key field, element).
+    // The rows' template is found relative to `from`, the template the lists
+    // are in, and has to exist even if there are no items, as for instances.
+    pub(crate) fn render_rows<'l, V: ContextValue>(
+        &self,
+        cache:&TemplateCache,
+        from:&str,
+        lists:impl Iterator<Item = (&'l str, &'l str, Option<&'l str>, B::Node)>,
+        data:&HashMap<&str, V>,
+        render:impl Fn(&str, &HashMap<&str, Value>) -> Result<B::Node, Errors>,
+    ) -> Result<(), Errors> {
+        let mut errors = Vec::new();
+        for (items, template, key, elem) in lists {
+            let checked = find_row(cache, template, from).and_then(|template| {
+                let items = list::items(data, items)?;
+                list::keys_of(items, key)?;
+                Ok((template, items))
+            });
+            let (template, items) = match checked {
+                Ok(checked) => checked,
+                Err(e) => {
+                    errors.push(e);
+                    continue;
+                },
+            };
+            for item in items {
+                match render(template, item) {
+                    Ok(row) => {
+                        for node in self.backend.child_nodes(&row) {
+                            self.backend.insert_before(&elem, &node, None);
+                        }
+                    },
+                    Err(e) => errors.extend(e.inner),
+                }
+            }
+        }
+
+        match errors.is_empty() {
+            true => Ok(()),
+            false => Err(Errors { inner: errors }),
+        }
+    }
+
+    /// Renders a template that has exactly one top-level element
+    ///
+    /// Whitespace and comments around the element are ignored. Other text is
+    /// an [`Error::RootText`], and any other number of elements is an
+    /// [`Error::Roots`], see [`Self::render_elems`] for templates with several
+    /// top-level elements.
+    pub fn render_elem<V: ContextValue>(&self, cache:&TemplateCache, name:&str, data:&HashMap<&str, V>) -> Result<B::Node, Errors> {
+        self.render_elem_with(cache, name, data, &cache.options)
+    }
+
+    pub fn render_elem_with<V: ContextValue>(&self, cache:&TemplateCache, name:&str, data:&HashMap<&str, V>, options:&RenderOptions) -> Result<B::Node, Errors> {
+        let frag = self.render_fragment_with(cache, name, data, options)?;
+        bind::only_elem(&self.backend, self.backend.child_nodes(&frag)).map_err(|e| Errors { inner: vec![e] })
+    }
+
+    pub fn render_elem_plain(&self, cache:&TemplateCache, name:&str) -> Result<B::Node, Error> {
+        let frag = self.render_fragment_plain(cache, name)?;
+        bind::only_elem(&self.backend, self.backend.child_nodes(&frag))
+    }
+
+    /// Renders the template and returns each of its top-level elements, in
+    /// order
+    ///
+    /// Text and comments between the elements are dropped.
+    pub fn render_elems<V: ContextValue>(&self, cache:&TemplateCache, name:&str, data:&HashMap<&str, V>) -> Result<Vec<B::Node>, Errors> {
+        self.render_elems_with(cache, name, data, &cache.options)
+    }
+
+    pub fn render_elems_with<V: ContextValue>(&self, cache:&TemplateCache, name:&str, data:&HashMap<&str, V>, options:&RenderOptions) -> Result<Vec<B::Node>, Errors> {
+        let frag = self.render_fragment_with(cache, name, data, options)?;
+        Ok(bind::roots(&self.backend, &frag))
+    }
+
+    pub fn render_elems_plain(&self, cache:&TemplateCache, name:&str) -> Result<Vec<B::Node>, Error> {
+        Ok(bind::roots(&self.backend, &self.render_fragment_plain(cache, name)?))
+    }
+
+    // The parsed version of `template`, which is the template under `name`
+    pub(crate) fn parsed(&self, name:&str, template:&Template) -> Parsed<B> {
+        let mut parsed = self.parsed.borrow_mut();
+
+        // The cache's `templates` is public, so the template under `name`
+        // could have been swapped out since it was parsed
+        let fresh = matches!(parsed.get(name), Some(p) if p.is_for(template));
+        if !fresh {
+            parsed.insert(name.to_string(), Parsed::new(&self.backend, template));
+        }
+
+        parsed[name].clone()
+    }
+
+    /// Drops the parsed version of the template called `name`, e.g. after
+    /// removing it from the cache
+    ///
+    /// Templates that were replaced are parsed again anyway, this just frees
+    /// the memory.
+    pub fn forget(&mut self, name:&str) {
+        self.parsed.get_mut().remove(name);
+    }
+}
+
+impl <B: DomBackend + Clone> DomRenderer<B> {
+    /// Renders the template called `name` into a [`TemplateInstance`], which
+    /// can be updated later
+    ///
+    /// The values are set as text, as in [`Self::render_fragment_text`]. This
+    /// fails with an [`Error::Unbindable`] if the template has a placeholder
+    /// that can't be updated in place.
+    pub fn render_instance<V: ContextValue>(&self, cache:&TemplateCache, name:&str, data:&HashMap<&str, V>) -> Result<TemplateInstance<B>, Errors> {
+        self.render_instance_with(cache, name, data, &cache.options)
+    }
+
+    /// The instance keeps using `options` in [`TemplateInstance::update`]
+    pub fn render_instance_with<V: ContextValue>(&self, cache:&TemplateCache, name:&str, data:&HashMap<&str, V>, options:&RenderOptions) -> Result<TemplateInstance<B>, Errors> {
+        self.render_instance_handlers_with(cache, name, data, Handlers::new(), options)
+    }
+
+    /// Same as [`Self::render_instance`], and also adds the event listeners
+    /// for `on:event="${key}"` attributes, using the closures in `handlers`
+    ///
+    /// Missing and extra handlers are treated the same way as missing and
+    /// extra values. The instance owns the closures, and removes the listeners
+    /// when it's dropped.
+    pub fn render_instance_handlers<V: ContextValue>(&self, cache:&TemplateCache, name:&str, data:&HashMap<&str, V>, handlers:Handlers<B>) -> Result<TemplateInstance<B>, Errors> {
+        self.render_instance_handlers_with(cache, name, data, handlers, &cache.options)
+    }
+
+    pub fn render_instance_handlers_with<V: ContextValue>(&self, cache:&TemplateCache, name:&str, data:&HashMap<&str, V>, handlers:Handlers<B>, options:&RenderOptions) -> Result<TemplateInstance<B>, Errors> {
+        let schema = self.schema(cache, name).map_err(|e| Errors { inner: vec![e] })?;
+        TemplateInstance::new(&self.backend, &schema, name, data, handlers, options)
+    }
+
+    // The parsed templates for an instance of the template called `name`,
+    // which are that one and the ones for the rows of its lists, and of
+    // theirs, by full name. These are taken up front, so that the lists can
+    // render rows without the cache.
+    pub(crate) fn schema(&self, cache:&TemplateCache, name:&str) -> Result<Schema<B>, Error> {
+        let mut schema = HashMap::new();
+        let mut queue = vec![name.to_string()];
+        while let Some(name) = queue.pop() {
+            if schema.contains_key(&name) {
+                continue;
+            }
+            let template = find(cache, &name)?;
+            let mut rows = HashMap::new();
+            for (_, mark) in template.marks.iter() {
+                if let Mark::Each { template: row, .. } = mark {
+                    let full = find_row(cache, row, &name)?;
+                    rows.insert(row.to_string(), full.to_string());
+                    queue.push(full.to_string());
+                }
+            }
+            let parsed = self.parsed(&name, template);
+            schema.insert(name, SchemaEntry { parsed, rows });
+        }
+        Ok(Rc::new(schema))
+    }
+}
+
+// The template called `name`, or an error rather than a panic if there's no
+// such template, since the DOM renders return errors for everything else
+pub(crate) fn find<'c>(cache:&'c TemplateCache, name:&str) -> Result<&'c Template<'c>, Error> {
+    cache.templates.get(name).ok_or_else(|| Error::UnknownTemplate(name.to_string()))
+}
+
+// The full name of the template for the rows of a `${#each ...}` in the
+// template called `from`, see `TemplateCache::resolve`
+pub(crate) fn find_row<'c>(cache:&'c TemplateCache, row:&str, from:&str) -> Result<&'c str, Error> {
+    cache.resolve(row, from).ok_or_else(|| Error::UnknownTemplate(row.to_string()))
+}
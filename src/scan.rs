@@ -0,0 +1,162 @@
+use crate::Error;
+use memchr::{memchr, memchr2, memmem};
+
+// What's inside the tags around a raw block, once any trim markers are taken
+// off
+const RAW_START: &str = "raw";
+const RAW_END: &str = "/raw";
+
+/// Something found by the [`Scanner`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Token {
+    /// A `${...}`, with start pointing to the `$` in the `${` and end just
+    /// after the matching `}`
+    Tag(usize, usize),
+    /// A `${raw}` and the next `${/raw}`, as the spans of the two tags. The
+    /// text between them is kept as it is
+    Raw((usize, usize), (usize, usize)),
+}
+
+/// The inside of a `${...}`, with its trim markers taken off
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct Tag {
+    /// Where the rest of the tag is in the input
+    pub key_start: usize,
+    pub key_end: usize,
+    /// `${-`: strip the whitespace before the tag
+    pub trim_before: bool,
+    /// `-}`: strip the whitespace after the tag
+    pub trim_after: bool,
+}
+
+impl Tag {
+    /// `start` and `end` are the span of the whole `${...}`
+    pub fn parse(input: &str, start: usize, end: usize) -> Self {
+        let mut key_start = start + "${".len();
+        let mut key_end = end - "}".len();
+
+        let trim_before = input[key_start..key_end].starts_with('-');
+        if trim_before {
+            key_start += "-".len();
+        }
+
+        let trim_after = key_end > key_start && input[key_start..key_end].ends_with('-');
+        if trim_after {
+            key_end -= "-".len();
+        }
+
+        // The whitespace between a marker and the key doesn't count, so that
+        // `${- key -}` reads nicely
+        if trim_before || trim_after {
+            let key = &input[key_start..key_end];
+            key_start += key.len() - key.trim_start().len();
+            key_end = key_start + key.trim().len();
+        }
+
+        Self {
+            key_start,
+            key_end,
+            trim_before,
+            trim_after,
+        }
+    }
+
+    /// Whether this is the tag `name`, e.g. `${raw}` or `${- raw -}`
+    fn is(&self, input: &str, name: &str) -> bool {
+        &input[self.key_start..self.key_end] == name
+    }
+}
+
+/// Finds every `${...}` in a template in a single pass
+///
+/// Each `${` is paired with the first `}` after it. Braces that aren't part
+/// of a `${`, like the ones in inline CSS or JS, are skipped over by the
+/// search and never looked at.
+///
+/// Everything from a `${raw}` to the next `${/raw}` is skipped over too, and
+/// returned as-is. Either of them can have trim markers, e.g. `${- raw}`.
+pub(crate) struct Scanner<'s> {
+    src: &'s str,
+    finder: memmem::Finder<'static>,
+    // Current position in the template string
+    cursor: usize,
+}
+
+impl<'s> Scanner<'s> {
+    pub fn new(src: &'s str) -> Self {
+        Self {
+            src,
+            finder: memmem::Finder::new("${"),
+            cursor: 0,
+        }
+    }
+
+    // Finds the span of the first `${/raw}` from `pos`, skipping over any
+    // other `${...}`
+    fn raw_end(&self, mut pos: usize) -> Option<(usize, usize)> {
+        let bytes = self.src.as_bytes();
+        loop {
+            let start = pos + self.finder.find(&bytes[pos..])?;
+            let end = start + memchr(b'}', &bytes[start..])? + "}".len();
+            if Tag::parse(self.src, start, end).is(self.src, RAW_END) {
+                return Some((start, end));
+            }
+            pos = start + "${".len();
+        }
+    }
+}
+
+impl<'s> Iterator for Scanner<'s> {
+    type Item = Result<Token, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let bytes = self.src.as_bytes();
+
+        let start = self.cursor + self.finder.find(&bytes[self.cursor..])?;
+
+        if let Some(end) = memchr(b'}', &bytes[start..]).map(|i| start + i + "}".len()) {
+            if Tag::parse(self.src, start, end).is(self.src, RAW_START) {
+                return Some(match self.raw_end(end) {
+                    Some(close) => {
+                        self.cursor = close.1;
+                        Ok(Token::Raw((start, end), close))
+                    },
+                    None => {
+                        self.cursor = bytes.len();
+                        Err(Error::Unclosed(start))
+                    }
+                });
+            }
+        }
+
+        let mut pos = start + "${".len();
+
+        // Look for the closing `}`, stopping at any `$` in case it starts a
+        // nested `${`
+        loop {
+            match memchr2(b'}', b'$', &bytes[pos..]) {
+                Some(i) => {
+                    let i = pos + i;
+                    if bytes[i] == b'}' {
+                        self.cursor = i + "}".len();
+                        return Some(Ok(Token::Tag(start, self.cursor)));
+                    } else if bytes.get(i + 1) == Some(&b'{') {
+                        // Don't bother scanning the rest: once one key is
+                        // broken we can't guess what the others are
+                        self.cursor = bytes.len();
+                        return Some(Err(Error::Nested(i)));
+                    } else {
+                        pos = i + 1;
+                    }
+                },
+                None => {
+                    // Bail immediately: if there's an unclosed delimiter, then
+                    // we basically can't guess about what provided key-value
+                    // pairs are needed
+                    self.cursor = bytes.len();
+                    return Some(Err(Error::Unclosed(start)));
+                }
+            }
+        }
+    }
+}
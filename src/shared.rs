@@ -0,0 +1,115 @@
+use crate::{ContextValue, Error, Errors, RenderOptions, Template};
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+/// The templates in a [`SharedTemplateCache`] at some point in time
+pub type Snapshot = Arc<HashMap<String, Arc<Template<'static>>>>;
+
+/// A template cache that can be shared between threads and changed at runtime
+///
+/// Unlike [`TemplateCache`](crate::TemplateCache), this owns its templates,
+/// and templates can be inserted, replaced and removed through a shared
+/// reference (e.g. from an `Arc<SharedTemplateCache>` in a web server).
+///
+/// Every change swaps in a new [`Snapshot`] rather than editing the map in
+/// place, so a render that is already running keeps using the snapshot it
+/// started with. Renders only hold the lock long enough to clone an `Arc`.
+///
+/// Rendering a name that isn't in the cache is an
+/// [`Error::UnknownTemplate`] rather than a panic, since templates can come
+/// and go.
+pub struct SharedTemplateCache {
+    templates: RwLock<Snapshot>,
+    options: RenderOptions,
+}
+
+impl SharedTemplateCache {
+    pub fn new() -> Self {
+        Self {
+            templates: RwLock::new(Arc::new(HashMap::new())),
+            options: RenderOptions::default(),
+        }
+    }
+
+    /// Sets the default options used by the render functions
+    pub fn with_options(mut self, options: RenderOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// The current set of templates, which won't change even if the cache does
+    pub fn snapshot(&self) -> Snapshot {
+        self.templates.read().unwrap().clone()
+    }
+
+    pub fn get(&self, name: &str) -> Option<Arc<Template<'static>>> {
+        self.templates.read().unwrap().get(name).cloned()
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.templates.read().unwrap().contains_key(name)
+    }
+
+    /// Parses `source` and adds it under `name`, returning the template that
+    /// was there before, if any
+    pub fn insert(&self, name: impl Into<String>, source: &str) -> Result<Option<Arc<Template<'static>>>, Error> {
+        let template = Template::new(source)?.into_owned();
+        Ok(self.insert_template(name, template))
+    }
+
+    /// Same as [`Self::insert`] for a template that's already parsed, e.g.
+    /// with non-default settings
+    pub fn insert_template(&self, name: impl Into<String>, template: Template<'static>) -> Option<Arc<Template<'static>>> {
+        let name = name.into();
+        let template = Arc::new(template);
+        self.update(|templates| templates.insert(name, template))
+    }
+
+    /// Parses `source` and swaps it in for the template under `name`,
+    /// returning the old one
+    ///
+    /// Nothing is added if there was no template under `name`, in which case
+    /// this returns `Ok(None)`.
+    pub fn replace(&self, name: &str, source: &str) -> Result<Option<Arc<Template<'static>>>, Error> {
+        let template = Arc::new(Template::new(source)?.into_owned());
+        Ok(self.update(|templates| {
+            templates.get_mut(name).map(|old| std::mem::replace(old, template))
+        }))
+    }
+
+    pub fn remove(&self, name: &str) -> Option<Arc<Template<'static>>> {
+        self.update(|templates| templates.remove(name))
+    }
+
+    pub fn render<V: ContextValue>(&self, name: &str, data: &HashMap<&str, V>) -> Result<String, Errors> {
+        self.render_with(name, data, &self.options)
+    }
+
+    pub fn render_with<V: ContextValue>(&self, name: &str, data: &HashMap<&str, V>, options: &RenderOptions) -> Result<String, Errors> {
+        // Don't hold the lock while rendering
+        let template = self.get(name).ok_or_else(|| Errors {
+            inner: vec![Error::UnknownTemplate(name.to_string())],
+        })?;
+
+        template.render_with(data, options)
+    }
+
+    pub fn render_plain(&self, name: &str) -> Option<String> {
+        self.get(name).map(|template| template.render_plain().to_string())
+    }
+
+    // Copy-on-write: readers that already have the old snapshot keep it
+    fn update<T>(&self, f: impl FnOnce(&mut HashMap<String, Arc<Template<'static>>>) -> T) -> T {
+        let mut templates = self.templates.write().unwrap();
+        let mut next = HashMap::clone(&templates);
+        let ret = f(&mut next);
+        *templates = Arc::new(next);
+        ret
+    }
+}
+
+impl Default for SharedTemplateCache {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,124 @@
+use crate::*;
+
+static TEST: &str = "<tr><td>${name}</td><td>${age}</td></tr>";
+
+fn rows() -> Vec<HashMap<&'static str, String>> {
+    (0..5).map(|i| hash_map! {
+        "name" => format!("person {}", i),
+        "age" => (20 + i).to_string(),
+    }).collect()
+}
+
+#[test]
+fn many() {
+    let template = Template::new(TEST).unwrap();
+    let rows = rows();
+
+    let rendered:Vec<String> = template.render_many(&rows).map(Result::unwrap).collect();
+
+    assert_eq!(rendered.len(), 5);
+    for (row, s) in rows.iter().zip(rendered.iter()) {
+        assert_eq!(*s, template.render(row).unwrap());
+    }
+}
+
+#[test]
+fn many_errors_are_per_context() {
+    let template = Template::new(TEST).unwrap();
+    let mut rows = rows();
+    rows[1].remove("age");
+    rows[3].insert("extra", "x".into());
+
+    let results:Vec<_> = template.render_many(&rows).collect();
+
+    assert!(results[0].is_ok());
+    assert_eq!(results[1].clone().unwrap_err().into_inner(), vec![Error::Missing("age".into())]);
+    assert!(results[2].is_ok());
+    assert_eq!(results[3].clone().unwrap_err().into_inner(), vec![Error::Extra("extra".into())]);
+    assert!(results[4].is_ok());
+
+    let lenient:Vec<_> = template.render_many_with(&rows, &RenderOptions::lenient()).collect();
+    assert!(lenient[3].is_ok());
+}
+
+#[test]
+fn same_size_different_shape() {
+    let template = Template::new(TEST).unwrap();
+    let mut rows = rows();
+
+    // Same number of entries as the others, which are fine
+    rows[3].remove("age");
+    rows[3].insert("extra", "x".into());
+
+    let results:Vec<_> = template.render_many(&rows).collect();
+
+    assert!(results[2].is_ok());
+    assert_eq!(
+        results[3].clone().unwrap_err().into_inner(),
+        vec![Error::Extra("extra".into()), Error::Missing("age".into())]
+    );
+    assert!(results[4].is_ok());
+}
+
+#[test]
+fn warn_checks_every_context() {
+    use std::sync::{Arc, Mutex};
+
+    let template = Template::new(TEST).unwrap();
+    let rows:Vec<_> = ["a", "b", "c"].iter().map(|extra| hash_map! {
+        "name" => "x",
+        "age" => "1",
+        *extra => "",
+    }).collect();
+
+    let warned = Arc::new(Mutex::new(Vec::new()));
+    let log = warned.clone();
+    let options = RenderOptions::strict().with_extra(ExtraPolicy::warn(move |k| log.lock().unwrap().push(k.to_string())));
+
+    template.render_join_with(&rows, "", &options).unwrap();
+    assert_eq!(*warned.lock().unwrap(), vec!["a", "b", "c"]);
+}
+
+#[test]
+fn join() {
+    let template = Template::new(TEST).unwrap();
+    let rows = rows();
+
+    let joined = template.render_join(&rows, "\n").unwrap();
+    let expected:Vec<String> = rows.iter().map(|row| template.render(row).unwrap()).collect();
+
+    assert_eq!(joined, expected.join("\n"));
+}
+
+#[test]
+fn join_empty() {
+    let template = Template::new(TEST).unwrap();
+    let rows:Vec<HashMap<&str, String>> = Vec::new();
+
+    assert_eq!(template.render_join(&rows, ",").unwrap(), "");
+}
+
+#[test]
+fn join_stops_at_error() {
+    let template = Template::new(TEST).unwrap();
+    let mut rows = rows();
+    rows[2].remove("name");
+
+    assert_eq!(
+        template.render_join(&rows, "\n").unwrap_err().into_inner(),
+        vec![Error::Missing("name".into())]
+    );
+}
+
+#[cfg(feature = "parallel")]
+#[test]
+fn parallel() {
+    let template = Template::new(TEST).unwrap();
+    let mut rows:Vec<_> = (0..100).flat_map(|_| rows()).collect();
+    rows[50].remove("name");
+
+    let results = template.par_render_many(&rows);
+    let expected:Vec<_> = template.render_many(&rows).collect();
+
+    assert_eq!(results, expected);
+}
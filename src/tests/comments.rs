@@ -0,0 +1,51 @@
+use crate::*;
+
+static TEST: &str = "${! shown in the sidebar }\n<p>Hello, ${! the user's display name }${name}</p>";
+
+#[test]
+fn not_rendered() {
+    let template = Template::new(TEST).unwrap();
+
+    let args = hash_map! {
+        "name" => "Charles",
+    };
+
+    assert_eq!(template.render(&args).unwrap(), "<p>Hello, Charles</p>");
+}
+
+#[test]
+fn not_in_plain() {
+    let template = Template::new(TEST).unwrap();
+
+    assert_eq!(template.render_plain(), "<p>Hello, ${name}</p>");
+}
+
+#[test]
+fn not_in_segments() {
+    let template = Template::new(TEST).unwrap();
+    let segments:Vec<_> = template.segments().collect();
+
+    assert_eq!(template.keys(), &["name"]);
+    assert_eq!(segments, vec![
+        Segment::Literal("<p>Hello, "),
+        Segment::Key("name"),
+        Segment::Literal("</p>"),
+    ]);
+}
+
+#[test]
+fn trim_through_comment() {
+    let template = Template::new("<ul>\n  ${! rows go here }\n  ${- rows -}\n</ul>\n${! end }").unwrap();
+
+    assert_eq!(template.render_plain(), "<ul>${rows}</ul>");
+}
+
+#[test]
+fn preserved_whitespace_stays() {
+    let template = Template::builder("a ${! comment } b")
+        .whitespace(Whitespace::Preserve)
+        .build()
+        .unwrap();
+
+    assert_eq!(template.render_plain(), "a  b");
+}
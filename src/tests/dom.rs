@@ -0,0 +1,369 @@
+use crate::*;
+use std::cell::Cell;
+use std::rc::Rc;
+
+fn renderer() -> DomRenderer<MemoryDom> {
+    DomRenderer::new(MemoryDom::new())
+}
+
+#[test]
+fn html_values() {
+    let cache = TemplateCache::new(&[
+        ("card", r#"<div class="card ${kind}"><h1>${title}</h1></div>"#),
+    ]);
+    let dom = renderer();
+
+    // Same as a string render, so escaped values stay escaped once
+    let args = html_map! {
+        "kind" => "wide",
+        "title" => "Tom & <Jerry>",
+    };
+    let html = r#"<div class="card wide"><h1>Tom &amp; &lt;Jerry&gt;</h1></div>"#;
+
+    assert_eq!(cache.render("card", &args).unwrap(), html);
+    assert_eq!(dom.render_fragment(&cache, "card", &args).unwrap().to_html(), html);
+
+    let args = hash_map! {
+        "kind" => "wide",
+        "title" => "<em>Tom</em>",
+    };
+
+    let frag = dom.render_fragment(&cache, "card", &args).unwrap();
+    assert_eq!(frag.to_html(), r#"<div class="card wide"><h1><em>Tom</em></h1></div>"#);
+}
+
+#[test]
+fn text_values() {
+    let cache = TemplateCache::new(&[
+        ("card", r#"<div class="card ${kind}"><h1>${title}</h1><!-- ${title} --></div>"#),
+    ]);
+    let dom = renderer();
+
+    let args = hash_map! {
+        "kind" => "wide",
+        "title" => "Tom & <Jerry>",
+    };
+
+    let frag = dom.render_fragment_text(&cache, "card", &args).unwrap();
+    assert_eq!(frag.to_html(), r#"<div class="card wide"><h1>Tom &amp; &lt;Jerry&gt;</h1><!-- Tom & <Jerry> --></div>"#);
+
+    // The second render clones the parsed template rather than parsing again
+    let args = hash_map! {
+        "kind" => "narrow",
+        "title" => "\"quoted\"",
+    };
+
+    let frag = dom.render_fragment_text(&cache, "card", &args).unwrap();
+    assert_eq!(frag.to_html(), r#"<div class="card narrow"><h1>"quoted"</h1><!-- "quoted" --></div>"#);
+}
+
+#[test]
+fn plain() {
+    let cache = TemplateCache::new(&[
+        ("link", r#"<a href="${url}">${label}</a>"#),
+    ]);
+    let dom = renderer();
+
+    assert_eq!(dom.render_fragment_plain(&cache, "link").unwrap().to_html(), r#"<a href="${url}">${label}</a>"#);
+    assert_eq!(dom.render_elem_plain(&cache, "link").unwrap().tag().unwrap(), "a");
+}
+
+#[test]
+fn missing() {
+    let cache = TemplateCache::new(&[
+        ("link", r#"<a href="${url}">${label}</a>"#),
+    ]);
+    let dom = renderer();
+
+    let args = hash_map! {
+        "label" => "home",
+    };
+
+    let errors = dom.render_fragment(&cache, "link", &args).unwrap_err();
+    assert_eq!(errors.inner, vec![Error::Missing("url".to_string())]);
+
+    let options = RenderOptions::lenient().with_missing(MissingPolicy::Marker);
+    let frag = dom.render_fragment_with(&cache, "link", &args, &options).unwrap();
+    assert_eq!(frag.to_html(), r#"<a href="${url}">home</a>"#);
+}
+
+#[test]
+fn unknown_template() {
+    let cache = TemplateCache::new(&[]);
+    let dom = renderer();
+    let args = hash_map! {
+        "title" => "a",
+    };
+    let unknown = vec![Error::UnknownTemplate("nosuch".to_string())];
+
+    assert_eq!(dom.render_fragment(&cache, "nosuch", &args).unwrap_err().inner, unknown);
+    assert_eq!(dom.render_fragment_text(&cache, "nosuch", &args).unwrap_err().inner, unknown);
+    assert_eq!(dom.render_elem(&cache, "nosuch", &args).unwrap_err().inner, unknown);
+    assert_eq!(dom.render_fragment_plain(&cache, "nosuch").unwrap_err(), unknown[0]);
+    assert_eq!(dom.render_elems_plain(&cache, "nosuch").unwrap_err(), unknown[0]);
+    assert_eq!(dom.render_instance(&cache, "nosuch", &args).err().unwrap().inner, unknown);
+    assert_eq!(dom.render_hydratable(&cache, "nosuch", &args).unwrap_err().inner, unknown);
+
+    let container = dom.backend().parse_fragment("<h1>a</h1>");
+    assert_eq!(dom.hydrate(&cache, "nosuch", &container, &args).err().unwrap().inner, unknown);
+}
+
+#[test]
+fn elems() {
+    let cache = TemplateCache::new(&[
+        ("one", "<!-- header --> <header>${title}</header> "),
+        ("two", "<dt>${term}</dt>\n<dd>${definition}</dd>"),
+        ("none", "just ${text}"),
+    ]);
+    let dom = renderer();
+
+    let args = hash_map! {
+        "title" => "a",
+        "term" => "b",
+        "definition" => "c",
+        "text" => "d",
+    };
+    let options = RenderOptions::lenient();
+
+    let elem = dom.render_elem_with(&cache, "one", &args, &options).unwrap();
+    assert_eq!(elem.to_html(), "<header>a</header>");
+
+    let elems = dom.render_elems_with(&cache, "two", &args, &options).unwrap();
+    let html:Vec<String> = elems.iter().map(MemoryNode::to_html).collect();
+    assert_eq!(html, vec!["<dt>b</dt>", "<dd>c</dd>"]);
+
+    let errors = dom.render_elem_with(&cache, "two", &args, &options).unwrap_err();
+    assert_eq!(errors.inner, vec![Error::Roots(2)]);
+
+    let errors = dom.render_elem_with(&cache, "none", &args, &options).unwrap_err();
+    assert_eq!(errors.inner, vec![Error::RootText("just d".to_string())]);
+}
+
+#[test]
+fn elem_with_text() {
+    let cache = TemplateCache::new(&[
+        ("label", "<b>${name}</b>: ${value}"),
+        ("empty", "<p>${name}</p>${value}"),
+    ]);
+    let dom = renderer();
+
+    let args = hash_map! {
+        "name" => "size",
+        "value" => "  10  ",
+    };
+
+    let errors = dom.render_elem(&cache, "label", &args).unwrap_err();
+    assert_eq!(errors.inner, vec![Error::RootText(":   10".to_string())]);
+    assert_eq!(dom.render_elem_plain(&cache, "label").unwrap_err(), Error::RootText(": ${value}".to_string()));
+
+    // Whitespace from a value is still only whitespace
+    let blank = hash_map! {
+        "name" => "size",
+        "value" => " \n ",
+    };
+    let elem = dom.render_elem(&cache, "empty", &blank).unwrap();
+    assert_eq!(elem.to_html(), "<p>size</p>");
+
+    let instance = dom.render_instance(&cache, "label", &args).unwrap();
+    assert_eq!(instance.elem().unwrap_err(), Error::RootText(":   10".to_string()));
+}
+
+#[test]
+fn unbindable() {
+    let cache = TemplateCache::new(&[
+        ("attrs", "<div ${attrs}>${text}</div>"),
+        ("tag", "<${tag}>${text}</${tag}>"),
+    ]);
+    let dom = renderer();
+
+    let args = hash_map! {
+        "attrs" => "hidden",
+        "tag" => "p",
+        "text" => "<b>",
+    };
+    let options = RenderOptions::lenient();
+
+    let frag = dom.render_fragment_with(&cache, "attrs", &args, &options).unwrap();
+    assert_eq!(frag.to_html(), r#"<div hidden=""><b></b></div>"#);
+
+    let frag = dom.render_fragment_with(&cache, "tag", &args, &options).unwrap();
+    assert_eq!(frag.to_html(), "<p><b></b></p>");
+    assert_eq!(dom.render_fragment_plain(&cache, "tag").unwrap().to_html(), "&lt;${tag}&gt;${text}&lt;/${tag}&gt;");
+
+    // These need every placeholder in text or an attribute value
+    let errors = dom.render_fragment_text_with(&cache, "attrs", &args, &options).unwrap_err();
+    assert_eq!(errors.inner, vec![Error::Unbindable("attrs".to_string())]);
+
+    let errors = dom.render_instance_with(&cache, "tag", &args, &options).err().unwrap();
+    assert_eq!(errors.inner, vec![Error::Unbindable("tag".to_string())]);
+}
+
+#[test]
+fn marks_in_template_text() {
+    // The characters that mark the placeholders while parsing, as entities
+    let cache = TemplateCache::new(&[
+        ("entity", "<p>&#xE000; ${a}</p>"),
+        ("spoof", r#"<p title="&#xE000;0&#xE001;">${a}</p>"#),
+        ("static", "<p>&#xE000;&#xE001;</p>"),
+    ]);
+    let dom = renderer();
+    let args = hash_map! { "a" => "x" };
+
+    for name in ["entity", "spoof"] {
+        let errors = dom.render_fragment_text(&cache, name, &args).unwrap_err();
+        assert_eq!(errors.inner, vec![Error::Unbindable("a".to_string())]);
+        assert!(dom.render_fragment(&cache, name, &args).is_ok());
+    }
+    assert_eq!(dom.render_fragment(&cache, "entity", &args).unwrap().to_html(), "<p>\u{E000} x</p>");
+
+    let frag = dom.render_fragment_text(&cache, "static", &HashMap::<&str, &str>::new()).unwrap();
+    assert_eq!(frag.to_html(), "<p>\u{E000}\u{E001}</p>");
+}
+
+#[test]
+fn refs() {
+    let cache = TemplateCache::new(&[
+        ("form", r#"<form data-ref="form"><input ${ref name} value="${name}"><button ${ref save}>Save</button></form>"#),
+    ]);
+    let dom = renderer();
+
+    let args = hash_map! {
+        "name" => "Ann",
+    };
+
+    let (frag, refs) = dom.render_fragment_refs(&cache, "form", &args).unwrap();
+    assert_eq!(frag.to_html(), r#"<form><input value="Ann"><button>Save</button></form>"#);
+    assert_eq!(refs.len(), 3);
+    assert_eq!(refs.get("name").unwrap().to_html(), r#"<input value="Ann">"#);
+    assert!(refs.get("save").unwrap().parent().unwrap().same_node(refs.get("form").unwrap()));
+}
+
+#[test]
+fn replaced_template() {
+    let mut cache = TemplateCache::new(&[
+        ("greeting", "<p>Hi ${name}</p>"),
+    ]);
+    let dom = renderer();
+
+    let args = hash_map! {
+        "name" => "Bo",
+    };
+
+    assert_eq!(dom.render_fragment_text(&cache, "greeting", &args).unwrap().to_html(), "<p>Hi Bo</p>");
+
+    cache.templates.insert("greeting".into(), Template::new("<p>Bye ${name}</p>").unwrap());
+    assert_eq!(dom.render_fragment_text(&cache, "greeting", &args).unwrap().to_html(), "<p>Bye Bo</p>");
+
+    // Same text, but no placeholder
+    cache.templates.insert("greeting".into(), Template::new("<p>Bye ${raw}${name}${/raw}</p>").unwrap());
+    assert_eq!(cache.templates["greeting"].render_plain(), "<p>Bye ${name}</p>");
+    let frag = dom.render_fragment_text(&cache, "greeting", &HashMap::<&str, &str>::new()).unwrap();
+    assert_eq!(frag.to_html(), "<p>Bye ${name}</p>");
+}
+
+#[test]
+fn instance_update() {
+    let cache = TemplateCache::new(&[
+        ("item", r#"<li class="${state}"><span>${label}</span> <em>${count}</em></li>"#),
+    ]);
+    let dom = renderer();
+
+    let args = hash_map! {
+        "state" => "open",
+        "label" => "Milk",
+        "count" => "1",
+    };
+
+    let mut instance = dom.render_instance(&cache, "item", &args).unwrap();
+    let li = instance.elem().unwrap();
+    assert_eq!(li.to_html(), r#"<li class="open"><span>Milk</span> <em>1</em></li>"#);
+
+    // Only the changed text is set, so the other nodes keep anything done to
+    // them in the meantime
+    let label = dom.backend().child_nodes(&dom.backend().child_nodes(&li)[0])[0].clone();
+    dom.backend().set_text(&label, "edited");
+
+    let args = hash_map! {
+        "state" => "open",
+        "label" => "Milk",
+        "count" => "2",
+    };
+    instance.update(&args).unwrap();
+    assert_eq!(li.to_html(), r#"<li class="open"><span>edited</span> <em>2</em></li>"#);
+
+    let args = hash_map! {
+        "state" => "done",
+        "label" => "Milk",
+    };
+    let errors = instance.update(&args).unwrap_err();
+    assert_eq!(errors.inner, vec![Error::Missing("count".to_string())]);
+    assert_eq!(li.to_html(), r#"<li class="open"><span>edited</span> <em>2</em></li>"#);
+}
+
+#[test]
+fn handlers() {
+    let cache = TemplateCache::new(&[
+        ("counter", r#"<button on:click="${increment}" class="${class}">+</button>"#),
+    ]);
+    let dom = renderer();
+
+    let args = hash_map! {
+        "class" => "big",
+    };
+
+    let clicks = Rc::new(Cell::new(0));
+    let counter = clicks.clone();
+    let handlers = Handlers::<MemoryDom>::new().on("increment", move |_| counter.set(counter.get() + 1));
+
+    let instance = dom.render_instance_handlers(&cache, "counter", &args, handlers).unwrap();
+    let button = instance.elem().unwrap();
+    assert_eq!(button.to_html(), r#"<button class="big">+</button>"#);
+
+    button.dispatch("click");
+    button.dispatch("click");
+    button.dispatch("keydown");
+    assert_eq!(clicks.get(), 2);
+
+    // The listeners go away with the instance
+    drop(instance);
+    button.dispatch("click");
+    assert_eq!(clicks.get(), 2);
+
+    // The handler key doesn't need a value in the other renders
+    let frag = dom.render_fragment(&cache, "counter", &args).unwrap();
+    assert_eq!(frag.to_html(), r#"<button class="big">+</button>"#);
+
+    let errors = dom.render_instance(&cache, "counter", &args).err().unwrap();
+    assert_eq!(errors.inner, vec![Error::Missing("increment".to_string())]);
+
+    let handlers = Handlers::<MemoryDom>::new().on("increment", |_| {}).on("decrement", |_| {});
+    let errors = dom.render_instance_handlers(&cache, "counter", &args, handlers).err().unwrap();
+    assert_eq!(errors.inner, vec![Error::Extra("decrement".to_string())]);
+}
+
+#[test]
+fn handler_keys_used_as_text() {
+    let cache = TemplateCache::new(&[
+        ("button", r#"<button on:click='${save}' on:focus="${focus}" title="${focus}">Save</button>"#),
+    ]);
+    let dom = renderer();
+
+    let args = hash_map! {
+        "focus" => "Focus",
+    };
+
+    // Only `save` is just a handler, `focus` is text too
+    let frag = dom.render_fragment(&cache, "button", &args).unwrap();
+    assert_eq!(frag.to_html(), r#"<button title="Focus">Save</button>"#);
+
+    let errors = dom.render_fragment(&cache, "button", &HashMap::<&str, &str>::new()).unwrap_err();
+    assert_eq!(errors.inner, vec![Error::Missing("focus".to_string())]);
+
+    let handlers = Handlers::<MemoryDom>::new().on("save", |_| {}).on("focus", |_| {});
+    let errors = dom.render_instance_handlers(&cache, "button", &HashMap::<&str, &str>::new(), handlers).err().unwrap();
+    assert_eq!(errors.inner, vec![Error::Missing("focus".to_string())]);
+
+    let handlers = Handlers::<MemoryDom>::new().on("save", |_| {}).on("focus", |_| {});
+    let instance = dom.render_instance_handlers(&cache, "button", &args, handlers).unwrap();
+    assert_eq!(instance.elem().unwrap().to_html(), r#"<button title="Focus">Save</button>"#);
+}
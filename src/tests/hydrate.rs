@@ -0,0 +1,285 @@
+use crate::*;
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+// What the browser would have parsed the server's page into
+fn page(dom:&MemoryDom, html:&str) -> MemoryNode {
+    let body = dom.parse_fragment(&format!("<main>{}</main>", html));
+    dom.child_nodes(&body)[0].clone()
+}
+
+#[test]
+fn server_markers() {
+    let cache = TemplateCache::new(&[
+        ("card", r#"<div class="card"><h1 class="${kind}">${title}</h1><p>static</p><button ${ref save} on:click="${save}">Save</button></div>"#),
+    ]);
+    let server = DomRenderer::new(MemoryDom::new());
+
+    let args = hash_map! {
+        "kind" => "wide",
+        "title" => "Tom & Jerry",
+    };
+
+    let html = server.render_hydratable(&cache, "card", &args).unwrap();
+    assert_eq!(html, r#"<div class="card"><h1 class="wide" data-sht="0">Tom &amp; Jerry</h1><p>static</p><button data-sht="1">Save</button></div>"#);
+}
+
+#[test]
+fn keeps_nodes() {
+    let cache = TemplateCache::new(&[
+        ("item", r#"<li class="${state}">${label}: <em>${count}</em><!-- ${label} --></li>"#),
+    ]);
+    let dom = MemoryDom::new();
+    let renderer = DomRenderer::new(dom);
+
+    let args = hash_map! {
+        "state" => "open",
+        "label" => "Milk",
+        "count" => "1",
+    };
+
+    let html = renderer.render_hydratable(&cache, "item", &args).unwrap();
+    let container = page(&dom, &html);
+    let li = dom.child_nodes(&container)[0].clone();
+    let em = dom.child_nodes(&li)[1].clone();
+
+    let mut instance = renderer.hydrate(&cache, "item", &container, &args).unwrap();
+    assert!(instance.elem().unwrap().same_node(&li));
+    assert_eq!(container.to_html(), r#"<main><li class="open">Milk: <em>1</em><!-- Milk --></li></main>"#);
+
+    let args = hash_map! {
+        "state" => "done",
+        "label" => "Eggs",
+        "count" => "12",
+    };
+
+    instance.update(&args).unwrap();
+    assert_eq!(container.to_html(), r#"<main><li class="done">Eggs: <em>12</em><!-- Eggs --></li></main>"#);
+    assert!(dom.child_nodes(&li)[1].same_node(&em));
+}
+
+#[test]
+fn empty_text() {
+    let cache = TemplateCache::new(&[
+        ("row", "${before}<b>${bold}</b>${after}"),
+    ]);
+    let dom = MemoryDom::new();
+    let renderer = DomRenderer::new(dom);
+
+    let args = hash_map! {
+        "before" => "",
+        "bold" => "",
+        "after" => "!",
+    };
+
+    // The empty text isn't in the server's DOM at all
+    let html = renderer.render_hydratable(&cache, "row", &args).unwrap();
+    assert_eq!(html, r#"<b data-sht="0"></b>!"#);
+    let container = page(&dom, &html);
+
+    let mut instance = renderer.hydrate(&cache, "row", &container, &args).unwrap();
+
+    let args = hash_map! {
+        "before" => "1",
+        "bold" => "2",
+        "after" => "3",
+    };
+
+    instance.update(&args).unwrap();
+    assert_eq!(container.to_html(), "<main>1<b>2</b>3</main>");
+}
+
+#[test]
+fn raw_text() {
+    let cache = TemplateCache::new(&[
+        ("head", "<title>${title}</title><textarea>${body}</textarea>"),
+    ]);
+    let dom = MemoryDom::new();
+    let renderer = DomRenderer::new(dom);
+
+    let args = hash_map! {
+        "title" => "<Home>",
+        "body" => "",
+    };
+
+    let html = renderer.render_hydratable(&cache, "head", &args).unwrap();
+    assert_eq!(html, r#"<title data-sht="0">&lt;Home&gt;</title><textarea data-sht="1"></textarea>"#);
+    let container = page(&dom, &html);
+
+    let mut instance = renderer.hydrate(&cache, "head", &container, &args).unwrap();
+
+    let args = hash_map! {
+        "title" => "About",
+        "body" => "text",
+    };
+
+    instance.update(&args).unwrap();
+    assert_eq!(container.to_html(), "<main><title>About</title><textarea>text</textarea></main>");
+}
+
+#[test]
+fn unsafe_values() {
+    let cache = TemplateCache::new(&[
+        ("comment", "<p><!-- ${note} --></p>"),
+        ("script", "<script>var x = '${x}';</script>"),
+        ("style", "<style>p { color: ${color} }</style>"),
+        ("bare", "<p><!--${note}--></p>"),
+    ]);
+    let renderer = DomRenderer::new(MemoryDom::new());
+
+    // These can't be escaped, so values that would end them are rejected
+    let errors = renderer.render_hydratable(&cache, "comment", &hash_map! { "note" => "--><img src=x onerror=alert(1)><!--" }).unwrap_err();
+    assert_eq!(errors.inner, vec![Error::UnsafeValue("note".to_string())]);
+    for note in ["a -- b", "a > b", "<!x"] {
+        assert!(renderer.render_hydratable(&cache, "comment", &hash_map! { "note" => note }).is_err());
+    }
+
+    let errors = renderer.render_hydratable(&cache, "script", &hash_map! { "x" => "</script><script>alert(1)</script>" }).unwrap_err();
+    assert_eq!(errors.inner, vec![Error::UnsafeValue("x".to_string())]);
+    let errors = renderer.render_hydratable(&cache, "style", &hash_map! { "color" => "red }</STYLE><img src=x>" }).unwrap_err();
+    assert_eq!(errors.inner, vec![Error::UnsafeValue("color".to_string())]);
+
+    // A comment that would pass for the start of a row
+    let errors = renderer.render_hydratable(&cache, "bare", &hash_map! { "note" => "sht-row" }).unwrap_err();
+    assert_eq!(errors.inner, vec![Error::UnsafeValue("note".to_string())]);
+
+    // Anything else is fine
+    let html = renderer.render_hydratable(&cache, "comment", &hash_map! { "note" => "a - b" }).unwrap();
+    assert_eq!(html, r#"<p data-sht="0"><!-- a - b --></p>"#);
+    let html = renderer.render_hydratable(&cache, "script", &hash_map! { "x" => "a < b </p>" }).unwrap();
+    assert_eq!(html, r#"<script data-sht="0">var x = 'a < b </p>';</script>"#);
+}
+
+#[test]
+fn handlers_and_refs() {
+    let cache = TemplateCache::new(&[
+        ("counter", r#"<p><button ${ref add} on:click="${increment}">+</button></p>"#),
+    ]);
+    let dom = MemoryDom::new();
+    let renderer = DomRenderer::new(dom);
+
+    let args:HashMap<&str, &str> = HashMap::new();
+
+    let html = renderer.render_hydratable(&cache, "counter", &args).unwrap();
+    let container = page(&dom, &html);
+
+    let clicks = Rc::new(Cell::new(0));
+    let counter = clicks.clone();
+    let handlers = Handlers::<MemoryDom>::new().on("increment", move |_| counter.set(counter.get() + 1));
+
+    let instance = renderer.hydrate_handlers(&cache, "counter", &container, &args, handlers).unwrap();
+    let button = instance.refs().get("add").unwrap();
+    assert_eq!(button.to_html(), "<button>+</button>");
+
+    button.dispatch("click");
+    assert_eq!(clicks.get(), 1);
+}
+
+#[test]
+fn lists() {
+    let cache = TemplateCache::new(&[
+        ("menu", r#"<nav><ul class="${class}" ${#each items template=item key=id}></ul><p>${footer}</p></nav>"#),
+        ("item", r#"<li class="${id}">${label}</li>"#),
+    ]);
+    let dom = MemoryDom::new();
+    let renderer = DomRenderer::new(dom);
+
+    let items = |ids:&[&'static str]| hash_map! {
+        "class" => Value::from("menu"),
+        "footer" => Value::from("end"),
+        "items" => Value::List(ids.iter().map(|id| hash_map! { "id" => Value::from(*id), "label" => Value::from(id.to_uppercase()) }).collect()),
+    };
+
+    // Each row is numbered on its own, after a marker
+    let html = renderer.render_hydratable(&cache, "menu", &items(&["a", "b"])).unwrap();
+    assert_eq!(html, r#"<nav><ul class="menu" data-sht="0"><!--sht-row--><li class="a" data-sht="0">A</li><!--sht-row--><li class="b" data-sht="0">B</li></ul><p data-sht="1">end</p></nav>"#);
+    let container = page(&dom, &html);
+    let ul = dom.child_nodes(&dom.child_nodes(&container)[0])[0].clone();
+    let server_rows:Vec<MemoryNode> = dom.child_nodes(&ul).into_iter().filter(|node| node.tag().is_some()).collect();
+
+    let mut instance = renderer.hydrate(&cache, "menu", &container, &items(&["a", "b"])).unwrap();
+    assert_eq!(container.to_html(), r#"<main><nav><ul class="menu"><li class="a">A</li><li class="b">B</li></ul><p>end</p></nav></main>"#);
+    let list = instance.list("items").unwrap();
+    assert_eq!(list.keys().collect::<Vec<_>>(), ["a", "b"]);
+    assert!(list.get("a").unwrap().elem().unwrap().same_node(&server_rows[0]));
+    assert!(list.get("b").unwrap().elem().unwrap().same_node(&server_rows[1]));
+
+    instance.update(&items(&["b", "c"])).unwrap();
+    assert_eq!(container.to_html(), r#"<main><nav><ul class="menu"><li class="b">B</li><li class="c">C</li></ul><p>end</p></nav></main>"#);
+    assert!(instance.list("items").unwrap().get("b").unwrap().elem().unwrap().same_node(&server_rows[1]));
+}
+
+#[test]
+fn row_text() {
+    let cache = TemplateCache::new(&[
+        ("para", "<p ${#each lines}></p>"),
+        ("lines", "${text}<br>"),
+    ]);
+    let dom = MemoryDom::new();
+    let renderer = DomRenderer::new(dom);
+
+    let lines = |texts:&[&'static str]| hash_map! {
+        "lines" => Value::List(texts.iter().map(|text| hash_map! { "text" => Value::from(*text) }).collect()),
+    };
+
+    // The empty text isn't in the server's HTML, so hydrating adds it to the
+    // right row
+    let html = renderer.render_hydratable(&cache, "para", &lines(&["", "b"])).unwrap();
+    assert_eq!(html, "<p data-sht=\"0\"><!--sht-row--><br><!--sht-row-->b<br></p>");
+    let container = page(&dom, &html);
+
+    let mut instance = renderer.hydrate(&cache, "para", &container, &lines(&["", "b"])).unwrap();
+    assert_eq!(container.to_html(), "<main><p><br>b<br></p></main>");
+
+    instance.update(&lines(&["x", "y"])).unwrap();
+    assert_eq!(container.to_html(), "<main><p>x<br>y<br></p></main>");
+    instance.update(&lines(&["y"])).unwrap();
+    assert_eq!(container.to_html(), "<main><p>y<br></p></main>");
+}
+
+#[test]
+fn row_count_mismatch() {
+    let cache = TemplateCache::new(&[
+        ("menu", "<ul ${#each items}></ul>"),
+        ("items", "<li>${label}</li>"),
+    ]);
+    let dom = MemoryDom::new();
+    let renderer = DomRenderer::new(dom);
+
+    let items = |labels:&[&'static str]| hash_map! {
+        "items" => Value::List(labels.iter().map(|label| hash_map! { "label" => Value::from(*label) }).collect()),
+    };
+
+    let html = renderer.render_hydratable(&cache, "menu", &items(&["a"])).unwrap();
+    let container = page(&dom, &html);
+    let errors = renderer.hydrate(&cache, "menu", &container, &items(&["a", "b"])).err().unwrap();
+    assert_eq!(errors.inner, vec![Error::Hydration(r#"1 rows in the list "items", but 2 items"#.to_string())]);
+}
+
+#[test]
+fn mismatch() {
+    let cache = TemplateCache::new(&[
+        ("a", r#"<p class="${class}">${text}</p>"#),
+        ("b", "<p><!-- ${text} --></p>"),
+        ("attrs", "<p ${attrs}></p>"),
+    ]);
+    let dom = MemoryDom::new();
+    let renderer = DomRenderer::new(dom);
+
+    let args = hash_map! {
+        "class" => "x",
+        "text" => "y",
+    };
+
+    let container = page(&dom, "<p>plain</p>");
+    let errors = renderer.hydrate(&cache, "a", &container, &args).err().unwrap();
+    assert_eq!(errors.inner, vec![Error::Hydration(r#"no element with data-sht="0""#.to_string())]);
+
+    let container = page(&dom, r#"<p data-sht="0">y</p>"#);
+    let errors = renderer.hydrate_with(&cache, "b", &container, &args, &RenderOptions::lenient()).err().unwrap();
+    assert_eq!(errors.inner, vec![Error::Hydration(r#"no node for key "text""#.to_string())]);
+
+    let errors = renderer.render_hydratable(&cache, "attrs", &hash_map! { "attrs" => "hidden" }).unwrap_err();
+    assert_eq!(errors.inner, vec![Error::Unbindable("attrs".to_string())]);
+}
@@ -0,0 +1,55 @@
+use crate::*;
+
+static TEST: &str = "Hello, ${name}. You remind me of another ${name}, ${friend}.${end}";
+
+#[test]
+fn keys() {
+    let template = Template::new(TEST).unwrap();
+
+    assert_eq!(template.keys(), vec!["name", "friend", "end"]);
+}
+
+#[test]
+fn placeholders() {
+    let template = Template::new(TEST).unwrap();
+    let placeholders:Vec<_> = template.placeholders().collect();
+
+    assert_eq!(placeholders.len(), 4);
+    assert_eq!(placeholders[0], Placeholder { key: "name", start: 7, end: 14 });
+
+    for p in placeholders {
+        assert_eq!(&template.render_plain()[p.start..p.end], format!("${{{}}}", p.key));
+    }
+}
+
+#[test]
+fn segments() {
+    let template = Template::new(TEST).unwrap();
+    let segments:Vec<_> = template.segments().collect();
+
+    assert_eq!(segments, vec![
+        Segment::Literal("Hello, "),
+        Segment::Key("name"),
+        Segment::Literal(". You remind me of another "),
+        Segment::Key("name"),
+        Segment::Literal(", "),
+        Segment::Key("friend"),
+        Segment::Literal("."),
+        Segment::Key("end"),
+    ]);
+}
+
+#[test]
+fn segments_adjacent() {
+    let template = Template::new("${a}${b}").unwrap();
+    let segments:Vec<_> = template.segments().collect();
+
+    assert_eq!(segments, vec![Segment::Key("a"), Segment::Key("b")]);
+}
+
+#[test]
+fn segments_empty() {
+    let template = Template::new("").unwrap();
+
+    assert_eq!(template.segments().count(), 0);
+}
@@ -0,0 +1,97 @@
+use crate::*;
+use std::cell::Cell;
+
+static TEST: &str = "${name} has ${count} messages. Yes, ${count}!";
+
+#[test]
+fn called_once() {
+    let calls = Cell::new(0);
+    let args = hash_map! {
+        "name" => Value::from("Charles"),
+        "count" => Value::lazy(|| {
+            calls.set(calls.get() + 1);
+            "3".to_string()
+        }),
+    };
+
+    let s = Template::new(TEST).unwrap().render(&args).unwrap();
+
+    assert_eq!(s, "Charles has 3 messages. Yes, 3!");
+    assert_eq!(calls.get(), 1);
+}
+
+#[test]
+fn unused_not_called() {
+    let calls = Cell::new(0);
+    let args = hash_map! {
+        "name" => Value::from(String::from("Charles")),
+        "count" => Value::from("3"),
+        "sidebar" => Value::lazy(|| {
+            calls.set(calls.get() + 1);
+            "<aside/>".to_string()
+        }),
+    };
+
+    let template = Template::new(TEST).unwrap();
+
+    assert!(template.render(&args).is_err());
+    assert_eq!(template.render_with(&args, &RenderOptions::lenient()).unwrap(), "Charles has 3 messages. Yes, 3!");
+    assert_eq!(calls.get(), 0);
+}
+
+#[test]
+fn once_per_render() {
+    let calls = Cell::new(0);
+    let rows:Vec<_> = (0..3).map(|i| hash_map! {
+        "name" => Value::from(format!("person {}", i)),
+        "count" => Value::lazy(|| {
+            calls.set(calls.get() + 1);
+            calls.get().to_string()
+        }),
+    }).collect();
+
+    let template = Template::new(TEST).unwrap();
+    let rendered:Vec<_> = template.render_many(&rows).map(Result::unwrap).collect();
+
+    assert_eq!(calls.get(), 3);
+    assert_eq!(rendered[2], "person 2 has 3 messages. Yes, 3!");
+}
+
+#[test]
+fn partial() {
+    let args = hash_map! {
+        "count" => Value::lazy(|| "3".to_string()),
+    };
+
+    let template = Template::new(TEST).unwrap().partial_render(&args);
+
+    assert_eq!(template.render_plain(), "${name} has 3 messages. Yes, 3!");
+}
+
+#[test]
+fn not_called_when_render_fails() {
+    let calls = Cell::new(0);
+    let args = hash_map! {
+        "count" => Value::lazy(|| {
+            calls.set(calls.get() + 1);
+            "3".to_string()
+        }),
+    };
+
+    let template = Template::new(TEST).unwrap();
+
+    assert_eq!(template.render(&args).unwrap_err().into_inner(), vec![Error::Missing("name".into())]);
+    assert_eq!(calls.get(), 0);
+}
+
+#[test]
+fn borrowed_values() {
+    let name = Value::from("Charles");
+    let count = Value::lazy(|| "3".to_string());
+    let args = hash_map! {
+        "name" => &name,
+        "count" => &count,
+    };
+
+    assert_eq!(Template::new(TEST).unwrap().render(&args).unwrap(), "Charles has 3 messages. Yes, 3!");
+}
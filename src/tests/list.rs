@@ -0,0 +1,237 @@
+use crate::*;
+use crate::list::longest_increasing;
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+// Counts the DOM changes, to check that rows aren't moved for nothing
+#[derive(Clone, Default)]
+struct CountingDom {
+    inner: MemoryDom,
+    inserts: Rc<Cell<usize>>,
+    removes: Rc<Cell<usize>>,
+}
+
+impl CountingDom {
+    fn reset(&self) -> (usize, usize) {
+        (self.inserts.replace(0), self.removes.replace(0))
+    }
+}
+
+impl DomBackend for CountingDom {
+    type Node = MemoryNode;
+    type Handler = <MemoryDom as DomBackend>::Handler;
+
+    fn parse_fragment(&self, html:&str) -> MemoryNode { self.inner.parse_fragment(html) }
+    fn clone_node(&self, node:&MemoryNode) -> MemoryNode { self.inner.clone_node(node) }
+    fn kind(&self, node:&MemoryNode) -> NodeKind { self.inner.kind(node) }
+    fn create_text(&self, text:&str) -> MemoryNode { self.inner.create_text(text) }
+    fn child_nodes(&self, node:&MemoryNode) -> Vec<MemoryNode> { self.inner.child_nodes(node) }
+    fn attributes(&self, node:&MemoryNode) -> Vec<(String, String)> { self.inner.attributes(node) }
+    fn get_attribute(&self, node:&MemoryNode, name:&str) -> Option<String> { self.inner.get_attribute(node, name) }
+    fn set_attribute(&self, node:&MemoryNode, name:&str, value:&str) { self.inner.set_attribute(node, name, value) }
+    fn remove_attribute(&self, node:&MemoryNode, name:&str) { self.inner.remove_attribute(node, name) }
+    fn text(&self, node:&MemoryNode) -> String { self.inner.text(node) }
+    fn set_text(&self, node:&MemoryNode, text:&str) { self.inner.set_text(node, text) }
+    fn add_listener(&self, node:&MemoryNode, event:&str, handler:&Self::Handler) { self.inner.add_listener(node, event, handler) }
+    fn remove_listener(&self, node:&MemoryNode, event:&str, handler:&Self::Handler) { self.inner.remove_listener(node, event, handler) }
+
+    fn insert_before(&self, parent:&MemoryNode, node:&MemoryNode, reference:Option<&MemoryNode>) {
+        self.inserts.set(self.inserts.get() + 1);
+        self.inner.insert_before(parent, node, reference)
+    }
+
+    fn remove(&self, node:&MemoryNode) {
+        self.removes.set(self.removes.get() + 1);
+        self.inner.remove(node)
+    }
+}
+
+// The values for the `todos` template, with a row for each (id, name)
+fn todos<'a>(rows:&[(&'a str, &'a str)]) -> HashMap<&'static str, Value<'a>> {
+    let items = rows.iter().map(|(id, name)| hash_map! { "id" => Value::from(*id), "name" => Value::from(*name) }).collect();
+    hash_map! {
+        "class" => Value::from("todos"),
+        "todos" => Value::List(items),
+    }
+}
+
+fn setup() -> (TemplateCache<'static>, DomRenderer<CountingDom>) {
+    let cache = TemplateCache::new(&[
+        ("todos", r#"<ul class="${class}" ${#each todos template=todo key=id}><li>header</li></ul>"#),
+        ("todo", r#"<li data-id="${id}">${name}</li>"#),
+    ]);
+    (cache, DomRenderer::new(CountingDom::default()))
+}
+
+#[test]
+fn each_attributes() {
+    let template = Template::new(r#"<ul ${#each  todos   template=todo key=id }></ul><ol ${#each rows key=id template=row}></ol><p ${#each row}></p>"#).unwrap();
+    assert_eq!(template.render_plain(), "<ul></ul><ol></ol><p></p>");
+    assert_eq!(template.keys(), &["todos", "rows", "row"]);
+
+    assert_eq!(Template::new("<ul ${#each }></ul>").unwrap_err(), Error::InvalidEach(4));
+    assert_eq!(Template::new("<ul ${#each key=id todos}></ul>").unwrap_err(), Error::InvalidEach(4));
+    assert_eq!(Template::new("<ul ${#each todos key=id key=x}></ul>").unwrap_err(), Error::InvalidEach(4));
+    assert_eq!(Template::new("<ul ${#each todos template=a template=b}></ul>").unwrap_err(), Error::InvalidEach(4));
+    assert_eq!(Template::new("<ul ${#each todos key=}></ul>").unwrap_err(), Error::InvalidEach(4));
+    assert_eq!(Template::new("<ul ${#each todos other}></ul>").unwrap_err(), Error::InvalidEach(4));
+}
+
+#[test]
+fn left_out_of_string_renders() {
+    let (cache, _) = setup();
+    assert_eq!(cache.render("todos", &todos(&[("a", "A")])).unwrap(), r#"<ul class="todos"><li>header</li></ul>"#);
+
+    // Quotes in the names don't need escaping, since they're never in the HTML
+    let cache = TemplateCache::new(&[
+        ("list", r#"<ul ${#each a"b template=x"y}></ul>"#),
+        ("x\"y", "<li>${name}</li>"),
+    ]);
+    let args = hash_map! { "a\"b" => Value::List(vec![hash_map! { "name" => Value::from("n") }]) };
+    assert_eq!(cache.render("list", &args).unwrap(), "<ul></ul>");
+    let renderer = DomRenderer::new(MemoryDom::new());
+    assert_eq!(renderer.render_fragment(&cache, "list", &args).unwrap().to_html(), "<ul><li>n</li></ul>");
+}
+
+#[test]
+fn fragments() {
+    let (cache, renderer) = setup();
+    let args = todos(&[("a", "A"), ("b", "B")]);
+
+    let html = r#"<ul class="todos"><li>header</li><li data-id="a">A</li><li data-id="b">B</li></ul>"#;
+    assert_eq!(renderer.render_fragment(&cache, "todos", &args).unwrap().to_html(), html);
+    assert_eq!(renderer.render_fragment_text(&cache, "todos", &args).unwrap().to_html(), html);
+    assert_eq!(renderer.render_fragment_plain(&cache, "todos").unwrap().to_html(), r#"<ul class="${class}"><li>header</li></ul>"#);
+}
+
+#[test]
+fn increasing() {
+    assert_eq!(longest_increasing(&[]), Vec::<bool>::new());
+    assert_eq!(longest_increasing(&[0, 1, 2]), vec![true, true, true]);
+    assert_eq!(longest_increasing(&[2, 0, 1]), vec![false, true, true]);
+    assert_eq!(longest_increasing(&[1, 2, 0]), vec![true, true, false]);
+    assert_eq!(longest_increasing(&[3, 1, 4, 0, 5, 2]).iter().filter(|keep| **keep).count(), 3);
+}
+
+#[test]
+fn reconcile() {
+    let (cache, renderer) = setup();
+    let dom = renderer.backend().clone();
+
+    let mut instance = renderer.render_instance(&cache, "todos", &todos(&[("a", "A"), ("b", "B"), ("c", "C")])).unwrap();
+    let ul = instance.elem().unwrap();
+    assert_eq!(ul.to_html(), r#"<ul class="todos"><li>header</li><li data-id="a">A</li><li data-id="b">B</li><li data-id="c">C</li></ul>"#);
+    assert_eq!(dom.reset(), (3, 0));
+    let b = dom.child_nodes(&ul)[2].clone();
+
+    // Moving the last row to the front only moves that one
+    instance.update(&todos(&[("c", "C"), ("a", "A"), ("b", "B")])).unwrap();
+    assert_eq!(ul.to_html(), r#"<ul class="todos"><li>header</li><li data-id="c">C</li><li data-id="a">A</li><li data-id="b">B</li></ul>"#);
+    assert_eq!(dom.reset(), (1, 0));
+
+    // Removing, adding and changing text in the same update
+    instance.update(&todos(&[("c", "C"), ("d", "D"), ("b", "Bee")])).unwrap();
+    assert_eq!(ul.to_html(), r#"<ul class="todos"><li>header</li><li data-id="c">C</li><li data-id="d">D</li><li data-id="b">Bee</li></ul>"#);
+    assert_eq!(dom.reset(), (1, 1));
+    assert!(dom.child_nodes(&ul)[3].same_node(&b));
+    assert_eq!(instance.list("todos").unwrap().keys().collect::<Vec<_>>(), vec!["c", "d", "b"]);
+
+    // Reversing keeps one row in place
+    instance.update(&todos(&[("b", "Bee"), ("d", "D"), ("c", "C")])).unwrap();
+    assert_eq!(ul.to_html(), r#"<ul class="todos"><li>header</li><li data-id="b">Bee</li><li data-id="d">D</li><li data-id="c">C</li></ul>"#);
+    assert_eq!(dom.reset(), (2, 0));
+
+    instance.update(&todos(&[])).unwrap();
+    assert_eq!(ul.to_html(), r#"<ul class="todos"><li>header</li></ul>"#);
+    assert_eq!(dom.reset(), (0, 3));
+    assert!(instance.list("todos").unwrap().is_empty());
+}
+
+#[test]
+fn errors_leave_list() {
+    let (cache, renderer) = setup();
+    let mut instance = renderer.render_instance(&cache, "todos", &todos(&[("a", "A"), ("b", "B")])).unwrap();
+    let ul = instance.elem().unwrap();
+    let html = ul.to_html();
+
+    let errors = instance.update(&todos(&[("a", "A"), ("a", "B")])).unwrap_err();
+    assert_eq!(errors.inner, vec![Error::DuplicateKey("a".to_string())]);
+
+    let mut args = todos(&[]);
+    args.insert("todos", Value::List(vec![hash_map! { "id" => Value::from("a"), "name" => Value::from("A") }, hash_map! { "name" => Value::from("B") }]));
+    let errors = instance.update(&args).unwrap_err();
+    assert_eq!(errors.inner, vec![Error::Missing("id".to_string())]);
+
+    // A row that fails to render doesn't stop the other rows from being
+    // checked, and nothing changes
+    args.insert("todos", Value::List(vec![
+        hash_map! { "id" => Value::from("b"), "name" => Value::from("B"), "extra" => Value::from("x") },
+        hash_map! { "id" => Value::from("c") },
+    ]));
+    let errors = instance.update(&args).unwrap_err();
+    assert_eq!(errors.inner, vec![Error::Extra("extra".to_string()), Error::Missing("name".to_string())]);
+
+    args.insert("todos", Value::from("not a list"));
+    let errors = instance.update(&args).unwrap_err();
+    assert_eq!(errors.inner, vec![Error::NotAList("todos".to_string())]);
+
+    args.remove("todos");
+    let errors = instance.update(&args).unwrap_err();
+    assert_eq!(errors.inner, vec![Error::Missing("todos".to_string())]);
+
+    assert_eq!(ul.to_html(), html);
+    assert_eq!(instance.list("todos").unwrap().len(), 2);
+}
+
+#[test]
+fn unknown_template() {
+    let cache = TemplateCache::new(&[("list", "<div ${#each nosuch}></div>")]);
+    let renderer = DomRenderer::new(MemoryDom::new());
+    let args = hash_map! { "nosuch" => Value::List(Vec::new()) };
+
+    let expected = vec![Error::UnknownTemplate("nosuch".to_string())];
+    assert_eq!(renderer.render_instance(&cache, "list", &args).err().unwrap().inner, expected);
+    assert_eq!(renderer.render_fragment(&cache, "list", &args).unwrap_err().inner, expected);
+    assert_eq!(renderer.render_fragment_text(&cache, "list", &args).unwrap_err().inner, expected);
+}
+
+#[test]
+fn unkeyed_and_handlers() {
+    let cache = TemplateCache::new(&[
+        ("list", "<div ${#each rows template=row}></div>"),
+        ("row", r#"<button on:click="${remove}">${label}</button>"#),
+    ]);
+    let renderer = DomRenderer::new(MemoryDom::new());
+    let rows = |labels:&[&'static str]| hash_map! {
+        "rows" => Value::List(labels.iter().map(|label| hash_map! { "label" => Value::from(*label) }).collect()),
+    };
+
+    let removed = Rc::new(Cell::new(None));
+    let handlers = {
+        let removed = removed.clone();
+        Handlers::<MemoryDom>::new().rows("rows", move |key| {
+            let key = key.to_string();
+            let removed = removed.clone();
+            Handlers::<MemoryDom>::new().on("remove", move |_| removed.set(Some(key.clone())))
+        })
+    };
+    let mut instance = renderer.render_instance_handlers(&cache, "list", &rows(&["x", "y"]), handlers).unwrap();
+    let div = instance.elem().unwrap();
+    assert_eq!(div.to_html(), "<div><button>x</button><button>y</button></div>");
+
+    instance.list("rows").unwrap().get("1").unwrap().elem().unwrap().dispatch("click");
+    assert_eq!(removed.take(), Some("1".to_string()));
+
+    // Without keys, rows are matched up by position, and new rows get their
+    // handlers too
+    instance.update(&rows(&["z", "w", "v"])).unwrap();
+    assert_eq!(div.to_html(), "<div><button>z</button><button>w</button><button>v</button></div>");
+    instance.list("rows").unwrap().get("2").unwrap().elem().unwrap().dispatch("click");
+    assert_eq!(removed.take(), Some("2".to_string()));
+
+    // Handlers for a list that isn't there are extra
+    let handlers = Handlers::<MemoryDom>::new().rows("nosuch", |_| Handlers::new());
+    let errors = renderer.render_instance_handlers(&cache, "list", &rows(&[]), handlers).err().unwrap();
+    assert_eq!(errors.inner, vec![Error::Extra("nosuch".to_string())]);
+}
@@ -0,0 +1,51 @@
+use crate::*;
+
+fn round_trip(html:&str) -> String {
+    MemoryDom::new().parse_fragment(html).to_html()
+}
+
+#[test]
+fn elements() {
+    assert_eq!(round_trip("<div id=a><p class='x y'>hi</p><br/><img src=\"i.png\"></div>"), r#"<div id="a"><p class="x y">hi</p><br><img src="i.png"></div>"#);
+    assert_eq!(round_trip("<INPUT Disabled Value=1>"), r#"<input disabled="" value="1">"#);
+    assert_eq!(round_trip(r#"<a href="1" href="2">x</a>"#), r#"<a href="1">x</a>"#);
+}
+
+#[test]
+fn text_and_entities() {
+    assert_eq!(round_trip("a &lt; b &amp;&amp; c &gt; d"), "a &lt; b &amp;&amp; c &gt; d");
+    assert_eq!(round_trip("1 < 2 & 3 <= 4"), "1 &lt; 2 &amp; 3 &lt;= 4");
+    assert_eq!(round_trip("&#65;&#x42;&quot;&apos;&unknown;"), "AB\"'&amp;unknown;");
+    assert_eq!(round_trip(r#"<p title="&quot;a&quot; &amp; b">x</p>"#), r#"<p title="&quot;a&quot; &amp; b">x</p>"#);
+}
+
+#[test]
+fn comments_and_doctype() {
+    assert_eq!(round_trip("<!DOCTYPE html><!-- <p>not a tag</p> --><p>x</p>"), "<!DOCTYPE html><!-- <p>not a tag</p> --><p>x</p>");
+}
+
+#[test]
+fn raw_text() {
+    assert_eq!(round_trip("<script>if (a < b && c) { x = '</p>'; }</script>"), "<script>if (a < b && c) { x = '</p>'; }</script>");
+    assert_eq!(round_trip("<textarea><b>&amp;</b></textarea>"), "<textarea>&lt;b&gt;&amp;&lt;/b&gt;</textarea>");
+}
+
+#[test]
+fn unmatched_end_tags() {
+    assert_eq!(round_trip("<div><span>a</div>b</span>"), "<div><span>a</span></div>b");
+    assert_eq!(round_trip("<ul><li>unclosed"), "<ul><li>unclosed</li></ul>");
+}
+
+#[test]
+fn clone_is_deep() {
+    let dom = MemoryDom::new();
+    let frag = dom.parse_fragment("<p>a</p>");
+    let copy = dom.clone_node(&frag);
+
+    let text = dom.child_nodes(&dom.child_nodes(&copy)[0])[0].clone();
+    dom.set_text(&text, "b");
+
+    assert_eq!(frag.to_html(), "<p>a</p>");
+    assert_eq!(copy.to_html(), "<p>b</p>");
+    assert!(text.parent().unwrap().parent().unwrap().same_node(&copy));
+}
@@ -1,3 +1,19 @@
 mod template;
 mod html;
-
+mod options;
+mod partial;
+mod introspect;
+mod whitespace;
+mod trim;
+mod comments;
+mod raw;
+mod precompiled;
+mod shared;
+mod namespace;
+mod batch;
+mod lazy;
+mod refs;
+mod dom;
+mod memory;
+mod hydrate;
+mod list;
@@ -0,0 +1,123 @@
+use crate::*;
+
+fn admin() -> TemplateCache<'static> {
+    TemplateCache::new(&[
+        ("header", "<h1>Admin</h1>"),
+        ("users::list", "<ul>${rows}</ul>"),
+    ])
+}
+
+fn shop() -> TemplateCache<'static> {
+    TemplateCache::new(&[
+        ("header", "<h1>Shop</h1>"),
+    ])
+}
+
+#[test]
+fn mount() {
+    let mut cache = TemplateCache::new(&[("header", "<h1>Site</h1>"), ("footer", "<footer/>")]);
+    cache.mount("admin", admin()).unwrap();
+    cache.mount("shop", shop()).unwrap();
+
+    assert_eq!(cache.render_plain("header"), "<h1>Site</h1>");
+    assert_eq!(cache.render_plain("admin::header"), "<h1>Admin</h1>");
+    assert_eq!(cache.render_plain("shop::header"), "<h1>Shop</h1>");
+    assert_eq!(cache.render_plain("admin::users::list"), "<ul>${rows}</ul>");
+}
+
+#[test]
+fn mount_collision() {
+    let mut cache = TemplateCache::new(&[("shop::header", "<h1>Taken</h1>")]);
+
+    assert_eq!(
+        cache.mount("shop", shop()).unwrap_err(),
+        Error::DuplicateTemplate("shop::header".into())
+    );
+
+    // Nothing was changed
+    assert_eq!(cache.render_plain("shop::header"), "<h1>Taken</h1>");
+    assert_eq!(cache.templates.len(), 1);
+}
+
+#[test]
+fn register_collision() {
+    let e = TemplateCache::try_new(&[("header", "a"), ("header", "b")]).err().unwrap();
+    assert_eq!(e, Error::DuplicateTemplate("header".into()));
+
+    let mut cache = shop();
+    assert_eq!(
+        cache.insert("header", Template::new("c").unwrap()).unwrap_err(),
+        Error::DuplicateTemplate("header".into())
+    );
+}
+
+#[test]
+fn mount_without_prefix() {
+    let mut cache = TemplateCache::new(&[("footer", "<footer/>")]);
+    cache.mount("", shop()).unwrap();
+
+    assert_eq!(cache.render_plain("header"), "<h1>Shop</h1>");
+    assert!(cache.get("::header").is_none());
+
+    assert_eq!(cache.mount("", shop()).unwrap_err(), Error::DuplicateTemplate("header".into()));
+}
+
+#[test]
+fn new_keeps_last_duplicate() {
+    let cache = TemplateCache::new(&[("header", "a"), ("header", "b")]);
+    assert_eq!(cache.render_plain("header"), "b");
+}
+
+#[test]
+fn resolve() {
+    let mut cache = TemplateCache::new(&[("header", "<h1>Site</h1>"), ("footer", "<footer/>")]);
+    cache.mount("admin", admin()).unwrap();
+    cache.mount("shop", shop()).unwrap();
+
+    assert_eq!(cache.resolve("header", "admin::users::list"), Some("admin::header"));
+    assert_eq!(cache.resolve("header", "shop::page"), Some("shop::header"));
+    assert_eq!(cache.resolve("footer", "admin::users::list"), Some("footer"));
+    assert_eq!(cache.resolve("header", "page"), Some("header"));
+    assert_eq!(cache.resolve("::header", "admin::users::list"), Some("header"));
+    assert_eq!(cache.resolve("users::list", "admin::header"), Some("admin::users::list"));
+    assert_eq!(cache.resolve("missing", "admin::header"), None);
+}
+
+#[test]
+fn list_rows() {
+    let mut cache = TemplateCache::new(&[
+        ("row", "<li>root ${name}</li>"),
+        ("footer", "<li>footer ${name}</li>"),
+    ]);
+    cache.mount("admin", TemplateCache::new(&[
+        ("page", "<ul ${#each rows template=row}></ul>"),
+        ("row", "<li>admin ${name}</li>"),
+        ("absolute", "<ul ${#each rows template=::row}></ul>"),
+        ("outer", "<ul ${#each rows template=footer}></ul>"),
+        ("missing", "<ul ${#each rows template=nosuch}></ul>"),
+    ])).unwrap();
+    let renderer = DomRenderer::new(MemoryDom::new());
+
+    let rows = |names:&[&'static str]| hash_map! {
+        "rows" => Value::List(names.iter().map(|name| hash_map! { "name" => Value::from(*name) }).collect()),
+    };
+    let args = rows(&["a"]);
+
+    // The mounted cache's own `row`, rather than the one at the root
+    assert_eq!(renderer.render_fragment(&cache, "admin::page", &args).unwrap().to_html(), "<ul><li>admin a</li></ul>");
+    assert_eq!(renderer.render_fragment_text(&cache, "admin::page", &args).unwrap().to_html(), "<ul><li>admin a</li></ul>");
+
+    let mut instance = renderer.render_instance(&cache, "admin::page", &args).unwrap();
+    assert_eq!(instance.list("rows").unwrap().template(), "admin::row");
+    instance.update(&rows(&["a", "b"])).unwrap();
+    assert_eq!(instance.elem().unwrap().to_html(), "<ul><li>admin a</li><li>admin b</li></ul>");
+
+    // Falling back to the root, or going straight there
+    assert_eq!(renderer.render_fragment(&cache, "admin::absolute", &args).unwrap().to_html(), "<ul><li>root a</li></ul>");
+    assert_eq!(renderer.render_fragment_text(&cache, "admin::outer", &args).unwrap().to_html(), "<ul><li>footer a</li></ul>");
+    let instance = renderer.render_instance(&cache, "admin::outer", &args).unwrap();
+    assert_eq!(instance.list("rows").unwrap().template(), "footer");
+
+    let errors = renderer.render_instance(&cache, "admin::missing", &args).err().unwrap();
+    assert_eq!(errors.inner, vec![Error::UnknownTemplate("nosuch".to_string())]);
+}
@@ -0,0 +1,90 @@
+use crate::*;
+use std::sync::{Arc, Mutex};
+
+static TEST: &str = "Hello, ${name}, it is ${time}.";
+
+#[test]
+fn extra_ignore() {
+    let args = hash_map! {
+        "name" => "Charles",
+        "time" => "2 AM",
+        "unused" => "whatever",
+    };
+
+    let options = RenderOptions::lenient();
+    let s = Template::new(TEST).unwrap().render_with(&args, &options).unwrap();
+
+    assert_eq!(s, "Hello, Charles, it is 2 AM.");
+}
+
+#[test]
+fn extra_fail() {
+    let args = hash_map! {
+        "name" => "Charles",
+        "time" => "2 AM",
+        "unused" => "whatever",
+    };
+
+    let e = Template::new(TEST).unwrap().render_with(&args, &RenderOptions::strict()).unwrap_err();
+
+    assert_eq!(e.into_inner(), vec![Error::Extra("unused".into())]);
+}
+
+#[test]
+fn extra_warn() {
+    let args = hash_map! {
+        "name" => "Charles",
+        "time" => "2 AM",
+        "unused" => "whatever",
+    };
+
+    let warned = Arc::new(Mutex::new(Vec::new()));
+    let options = RenderOptions::strict().with_extra(ExtraPolicy::warn({
+        let warned = warned.clone();
+        move |k| warned.lock().unwrap().push(k.to_string())
+    }));
+
+    let s = Template::new(TEST).unwrap().render_with(&args, &options).unwrap();
+
+    assert_eq!(s, "Hello, Charles, it is 2 AM.");
+    assert_eq!(*warned.lock().unwrap(), vec!["unused".to_string()]);
+}
+
+#[test]
+fn missing_empty() {
+    let args = hash_map! {
+        "name" => "Charles",
+    };
+
+    let options = RenderOptions::strict().with_missing(MissingPolicy::Empty);
+    let s = Template::new(TEST).unwrap().render_with(&args, &options).unwrap();
+
+    assert_eq!(s, "Hello, Charles, it is .");
+}
+
+#[test]
+fn missing_marker() {
+    let args = hash_map! {
+        "name" => "Charles",
+    };
+
+    let options = RenderOptions::strict().with_missing(MissingPolicy::Marker);
+    let s = Template::new(TEST).unwrap().render_with(&args, &options).unwrap();
+
+    assert_eq!(s, "Hello, Charles, it is ${time}.");
+}
+
+#[test]
+fn cache_default() {
+    let cache = TemplateCache::new(&[("test", TEST)])
+        .with_options(RenderOptions::lenient());
+
+    let args = hash_map! {
+        "name" => "Charles",
+        "time" => "2 AM",
+        "unused" => "whatever",
+    };
+
+    assert_eq!(cache.render("test", &args).unwrap(), "Hello, Charles, it is 2 AM.");
+    assert!(cache.render_with("test", &args, &RenderOptions::strict()).is_err());
+}
@@ -0,0 +1,69 @@
+use crate::*;
+
+static TEST: &str = "<h1>${site_name}</h1><p>Hello, ${name}. Welcome to ${site_name}!</p>";
+
+#[test]
+fn keeps_missing() {
+    let site = hash_map! {
+        "site_name" => "Cats",
+    };
+
+    let template = Template::new(TEST).unwrap().partial_render(&site);
+
+    assert_eq!(template.render_plain(), "<h1>Cats</h1><p>Hello, ${name}. Welcome to Cats!</p>");
+    assert_eq!(template.keys(), vec!["name"]);
+}
+
+#[test]
+fn same_as_full() {
+    let full = hash_map! {
+        "site_name" => "Cats",
+        "name" => "Charles",
+    };
+
+    let site = hash_map! {
+        "site_name" => "Cats",
+    };
+
+    let request = hash_map! {
+        "name" => "Charles",
+    };
+
+    let original = Template::new(TEST).unwrap();
+    let partial = original.partial_render(&site);
+
+    assert_eq!(partial.render(&request).unwrap(), original.render(&full).unwrap());
+}
+
+#[test]
+fn value_looks_like_key() {
+    let site = hash_map! {
+        "site_name" => "${name}",
+    };
+
+    let request = hash_map! {
+        "name" => "Charles",
+    };
+
+    let template = Template::new(TEST).unwrap().partial_render(&site);
+
+    assert_eq!(
+        template.render(&request).unwrap(),
+        "<h1>${name}</h1><p>Hello, Charles. Welcome to ${name}!</p>"
+    );
+}
+
+#[test]
+fn nothing_left() {
+    let full = hash_map! {
+        "site_name" => "Cats",
+        "name" => "Charles",
+        "unused" => "whatever",
+    };
+
+    let template = Template::new(TEST).unwrap().partial_render(&full);
+    let empty:HashMap<&str, &str> = HashMap::new();
+
+    assert_eq!(template.placeholders().count(), 0);
+    assert_eq!(template.render(&empty).unwrap(), "<h1>Cats</h1><p>Hello, Charles. Welcome to Cats!</p>");
+}
@@ -0,0 +1,174 @@
+use crate::*;
+
+static TEST: &str = "  <p>Hello, ${name}. You remind me of another ${name}, ${! not this } ${friend}.</p>  ";
+
+#[test]
+fn round_trip() {
+    let template = Template::new(TEST).unwrap();
+    let bytes = template.to_bytes();
+    let loaded = Template::from_bytes(&bytes).unwrap();
+
+    assert_eq!(loaded.render_plain(), template.render_plain());
+    assert_eq!(loaded.keys(), template.keys());
+    assert!(loaded.placeholders().eq(template.placeholders()));
+
+    let args = hash_map! {
+        "name" => "Charles",
+        "friend" => "Dave",
+    };
+
+    assert_eq!(loaded.render(&args).unwrap(), template.render(&args).unwrap());
+}
+
+#[test]
+fn refs_round_trip() {
+    let template = Template::new(r#"<form ${ref form}><input ${ref name} value="${name}"></form>"#).unwrap();
+    let bytes = template.to_bytes();
+    let loaded = Template::from_bytes(&bytes).unwrap();
+
+    let mut cache = TemplateCache::new(&[]);
+    cache.templates.insert("form".into(), loaded);
+    let dom = DomRenderer::new(MemoryDom::new());
+
+    let (frag, refs) = dom.render_fragment_refs(&cache, "form", &hash_map!{"name" => "Ann"}).unwrap();
+    assert_eq!(frag.to_html(), r#"<form><input value="Ann"></form>"#);
+    assert_eq!(refs.get("name").unwrap().parent().unwrap().tag().unwrap(), "form");
+    assert!(refs.get("form").is_some());
+
+    // A mark past the end of the text
+    let mut bad = bytes;
+    let position = bad.len() - (4 + 1 + 4 + "name".len());
+    bad[position..position + 4].copy_from_slice(&1000u32.to_le_bytes());
+    assert_eq!(Template::from_bytes(&bad).err(), Some(LoadError::Corrupt));
+}
+
+#[test]
+fn lists_round_trip() {
+    let template = Template::new(r#"<ul ${#each rows template=row key=id}></ul><ol ${#each others}></ol>"#).unwrap();
+    let bytes = template.to_bytes();
+    let loaded = Template::from_bytes(&bytes).unwrap();
+    assert_eq!(loaded.keys(), &["rows", "others"]);
+
+    let mut cache = TemplateCache::new(&[("row", "<li>${id}</li>"), ("others", "<li>-</li>")]);
+    cache.templates.insert("list".into(), loaded);
+    let dom = DomRenderer::new(MemoryDom::new());
+
+    let args = hash_map! {
+        "rows" => Value::List(vec![hash_map! { "id" => Value::from("1") }, hash_map! { "id" => Value::from("2") }]),
+        "others" => Value::List(vec![HashMap::new()]),
+    };
+    let frag = dom.render_fragment(&cache, "list", &args).unwrap();
+    assert_eq!(frag.to_html(), "<ul><li>1</li><li>2</li></ul><ol><li>-</li></ol>");
+
+    // A list whose items aren't one of the keys
+    let mut bad = bytes;
+    let position = bad.len() - ("others".len() + 4 + "others".len() + 1);
+    bad[position..position + "others".len()].copy_from_slice(b"nosuch");
+    assert_eq!(Template::from_bytes(&bad).err(), Some(LoadError::Corrupt));
+}
+
+#[test]
+fn source_check() {
+    let bytes = Template::new(TEST).unwrap().to_bytes();
+
+    assert!(Template::from_bytes_checked(&bytes, TEST).is_ok());
+    assert_eq!(
+        Template::from_bytes_checked(&bytes, "<p>${name}</p>").err(),
+        Some(LoadError::SourceMismatch(None))
+    );
+}
+
+#[test]
+fn source_check_settings() {
+    let builder = Template::builder(TEST).whitespace(Whitespace::Preserve);
+    let bytes = builder.clone().build().unwrap().to_bytes();
+
+    assert!(builder.from_bytes_checked(&bytes).is_ok());
+
+    // Same source, but it would be parsed differently now
+    assert_eq!(
+        Template::from_bytes_checked(&bytes, TEST).err(),
+        Some(LoadError::SourceMismatch(None))
+    );
+}
+
+#[test]
+fn version() {
+    let mut bytes = Template::new(TEST).unwrap().to_bytes();
+    bytes[4] = 99;
+
+    assert_eq!(Template::from_bytes(&bytes).err(), Some(LoadError::Version(99)));
+}
+
+#[test]
+fn magic() {
+    let cache_bytes = TemplateCache::new(&[("test", TEST)]).to_bytes();
+
+    assert_eq!(Template::from_bytes(b"hello").err(), Some(LoadError::Magic));
+    assert_eq!(Template::from_bytes(&cache_bytes).err(), Some(LoadError::Magic));
+}
+
+#[test]
+fn corrupt() {
+    let bytes = Template::new(TEST).unwrap().to_bytes();
+
+    // Every truncation should be caught rather than panic
+    for len in 6..bytes.len() {
+        assert_eq!(Template::from_bytes(&bytes[..len]).err(), Some(LoadError::Corrupt));
+    }
+
+    // A placeholder pointing past the end of the text, which is the last
+    // thing before the mark count
+    let mut bad = bytes.clone();
+    let end = bad.len() - 8;
+    bad[end..end + 4].copy_from_slice(&1000u32.to_le_bytes());
+    assert_eq!(Template::from_bytes(&bad).err(), Some(LoadError::Corrupt));
+
+    let mut extra = bytes;
+    extra.push(0);
+    assert_eq!(Template::from_bytes(&extra).err(), Some(LoadError::Corrupt));
+}
+
+#[test]
+fn cache_round_trip() {
+    let sources = [("hello", TEST), ("bye", "Bye, ${name}!")];
+    let bytes = TemplateCache::new(&sources).to_bytes();
+    let cache = TemplateCache::from_bytes_checked(&bytes, &sources).unwrap();
+
+    let args = hash_map! {
+        "name" => "Charles",
+    };
+
+    assert_eq!(cache.render("bye", &args).unwrap(), "Bye, Charles!");
+
+    assert_eq!(
+        TemplateCache::from_bytes_checked(&bytes, &[("bye", "Goodbye, ${name}!")]).err(),
+        Some(LoadError::SourceMismatch(Some("bye".into())))
+    );
+}
+
+#[test]
+fn cache_duplicate() {
+    let one = TemplateCache::new(&[("a", "${a}")]).to_bytes();
+
+    // The header, then a count of 2 and the same template twice
+    let (header, rest) = one.split_at(6);
+    let entry = &rest[4..];
+    let mut bytes = header.to_vec();
+    bytes.extend_from_slice(&2u32.to_le_bytes());
+    bytes.extend_from_slice(entry);
+    bytes.extend_from_slice(entry);
+
+    assert_eq!(
+        TemplateCache::from_bytes(&bytes).err(),
+        Some(LoadError::DuplicateTemplate("a".into()))
+    );
+}
+
+#[test]
+fn cache_is_deterministic() {
+    let a = TemplateCache::new(&[("a", "${a}"), ("b", "${b}"), ("c", "${c}")]).to_bytes();
+    let b = TemplateCache::new(&[("c", "${c}"), ("b", "${b}"), ("a", "${a}")]).to_bytes();
+
+    assert_eq!(a, b);
+}
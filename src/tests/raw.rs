@@ -0,0 +1,54 @@
+use crate::*;
+
+static TEST: &str = "<div>${name}</div>${raw}<script type=\"text/x-handlebars\">{{#if x}}${price}{{/if}}</script>${/raw}<p>${name}</p>";
+
+#[test]
+fn verbatim() {
+    let template = Template::new(TEST).unwrap();
+
+    let args = hash_map! {
+        "name" => "Charles",
+    };
+
+    assert_eq!(template.keys(), &["name"]);
+    assert_eq!(
+        template.render(&args).unwrap(),
+        "<div>Charles</div><script type=\"text/x-handlebars\">{{#if x}}${price}{{/if}}</script><p>Charles</p>"
+    );
+}
+
+#[test]
+fn segments() {
+    let template = Template::new("a ${raw}${b}${/raw} ${c}").unwrap();
+    let segments:Vec<_> = template.segments().collect();
+
+    assert_eq!(segments, vec![
+        Segment::Literal("a ${b} "),
+        Segment::Key("c"),
+    ]);
+}
+
+#[test]
+fn unbalanced_braces() {
+    let template = Template::new("${raw}${ { } ${${/raw}").unwrap();
+
+    assert_eq!(template.render_plain(), "${ { } ${");
+    assert_eq!(template.placeholders().count(), 0);
+}
+
+#[test]
+fn whitespace_kept() {
+    let template = Template::builder("\n${raw}\n  <pre>\n</pre>\n${/raw}\n")
+        .whitespace(Whitespace::Collapse)
+        .build()
+        .unwrap();
+
+    assert_eq!(template.render_plain(), "\n  <pre>\n</pre>\n");
+}
+
+#[test]
+fn unclosed() {
+    let e = Template::new("<p>${raw}${x}</p>").err().unwrap();
+
+    assert_eq!(e, Error::Unclosed(3));
+}
@@ -0,0 +1,63 @@
+use crate::*;
+
+#[test]
+fn left_out_of_render() {
+    let template = Template::new(r#"<button ${ref save} class="${class}">Save</button>"#).unwrap();
+
+    assert_eq!(template.render_plain(), r#"<button class="${class}">Save</button>"#);
+    assert_eq!(template.keys(), &["class"]);
+
+    let args = hash_map! {
+        "class" => "primary",
+    };
+
+    assert_eq!(template.render(&args).unwrap(), r#"<button class="primary">Save</button>"#);
+}
+
+#[test]
+fn in_dom_renders() {
+    let cache = TemplateCache::new(&[
+        ("input", "<input ${ref  name }>"),
+    ]);
+    let dom = DomRenderer::new(MemoryDom::new());
+
+    assert_eq!(cache.render_plain("input"), "<input>");
+
+    let (frag, refs) = dom.render_fragment_refs(&cache, "input", &HashMap::<&str, &str>::new()).unwrap();
+    assert_eq!(frag.to_html(), "<input>");
+    assert!(refs.get("name").unwrap().same_node(&dom.backend().child_nodes(&frag)[0]));
+}
+
+#[test]
+fn invalid_name() {
+    assert_eq!(Template::new("<input ${ref two words}>").unwrap_err(), Error::InvalidRef(7));
+    assert_eq!(Template::new(r#"<input ${ref a"b}>"#).unwrap_err(), Error::InvalidRef(7));
+    assert_eq!(Template::new("<input ${ref 1st}>").unwrap_err(), Error::InvalidRef(7));
+    assert!(Template::new("<input ${ref _first-name2}>").is_ok());
+}
+
+#[test]
+fn not_a_key() {
+    let template = Template::new("<div ${ref box}></div>").unwrap();
+    let segments:Vec<_> = template.segments().collect();
+
+    assert!(template.keys().is_empty());
+    assert_eq!(segments, vec![Segment::Literal("<div></div>")]);
+}
+
+#[test]
+fn moved_by_partial_render() {
+    let template = Template::new(r#"<p class="${class}">${a} <b ${ref b}>${b}</b></p>"#).unwrap();
+    let partial = template.partial_render(&hash_map! {
+        "a" => "first",
+        "class" => "x",
+    });
+    assert_eq!(partial.render_plain(), r#"<p class="x">first <b>${b}</b></p>"#);
+
+    let mut cache = TemplateCache::new(&[]);
+    cache.templates.insert("p".into(), partial);
+    let dom = DomRenderer::new(MemoryDom::new());
+
+    let (_, refs) = dom.render_fragment_refs(&cache, "p", &hash_map!{"b" => "second"}).unwrap();
+    assert_eq!(refs.get("b").unwrap().to_html(), "<b>second</b>");
+}
@@ -0,0 +1,99 @@
+use crate::*;
+use std::sync::Arc;
+use std::thread;
+
+#[test]
+fn insert_replace_remove() {
+    let cache = SharedTemplateCache::new();
+    let args = hash_map! {
+        "name" => "Charles",
+    };
+
+    assert!(cache.insert("hello", "Hello, ${name}!").unwrap().is_none());
+    assert_eq!(cache.render("hello", &args).unwrap(), "Hello, Charles!");
+
+    // Replacing something that isn't there doesn't add it
+    assert!(cache.replace("bye", "Bye, ${name}!").unwrap().is_none());
+    assert!(!cache.contains("bye"));
+
+    let old = cache.replace("hello", "Hi, ${name}!").unwrap().unwrap();
+    assert_eq!(old.render_plain(), "Hello, ${name}!");
+    assert_eq!(cache.render("hello", &args).unwrap(), "Hi, Charles!");
+
+    assert!(cache.remove("hello").is_some());
+    assert_eq!(
+        cache.render("hello", &args).unwrap_err().into_inner(),
+        vec![Error::UnknownTemplate("hello".into())]
+    );
+}
+
+#[test]
+fn parse_error() {
+    let cache = SharedTemplateCache::new();
+
+    assert_eq!(cache.insert("bad", "${oops").unwrap_err(), Error::Unclosed(0));
+    assert!(!cache.contains("bad"));
+}
+
+#[test]
+fn snapshot_is_consistent() {
+    let cache = SharedTemplateCache::new();
+    cache.insert("a", "a: ${x}").unwrap();
+
+    let snapshot = cache.snapshot();
+    cache.replace("a", "changed").unwrap();
+    cache.insert("b", "b").unwrap();
+
+    assert_eq!(snapshot.len(), 1);
+    assert_eq!(snapshot["a"].render_plain(), "a: ${x}");
+    assert_eq!(cache.snapshot().len(), 2);
+}
+
+#[test]
+fn threads() {
+    let cache = Arc::new(SharedTemplateCache::new().with_options(RenderOptions::lenient()));
+    cache.insert("page", "<p>${n}</p>").unwrap();
+
+    let readers:Vec<_> = (0..4).map(|i| {
+        let cache = cache.clone();
+        thread::spawn(move || {
+            for _ in 0..200 {
+                let n = i.to_string();
+                let args = hash_map! {
+                    "n" => n.as_str(),
+                };
+                let s = cache.render("page", &args).unwrap();
+                assert!(s == format!("<p>{}</p>", i) || s == format!("<div>{}</div>", i));
+            }
+        })
+    }).collect();
+
+    let writer = {
+        let cache = cache.clone();
+        thread::spawn(move || {
+            for i in 0..200 {
+                let source = if i % 2 == 0 { "<div>${n}</div>" } else { "<p>${n}</p>" };
+                cache.replace("page", source).unwrap();
+            }
+        })
+    };
+
+    for t in readers {
+        t.join().unwrap();
+    }
+    writer.join().unwrap();
+}
+
+#[test]
+fn owned_template() {
+    let source = String::from("${greeting}, ${name}");
+    let template = Template::new(&source).unwrap().into_owned();
+    drop(source);
+
+    let args = hash_map! {
+        "greeting" => "Hi",
+        "name" => "Charles",
+    };
+
+    assert_eq!(template.render(&args).unwrap(), "Hi, Charles");
+}
@@ -124,6 +124,20 @@ mod two {
 
         assert_eq!(s, "Hello, Charles. You remind me of another Charles.");
     }
+
+    // Repeated keys are only reported once
+    #[test]
+    fn err_missing() {
+        let args:HashMap<&str, &str> = HashMap::new();
+
+        let e = Template::new(TEST).unwrap().render(&args).unwrap_err();
+
+        let expected = Errors {
+            inner: vec![Error::Missing("name".into())],
+        };
+
+        assert_eq!(e, expected);
+    }
 }
 
 mod twice {
@@ -239,9 +253,8 @@ mod unclosed {
     }
 }
 
-// This is sort of a weird way to error about mismatched delimiters, but I guess
-// it's workable. I'm open to better ideas though, given a working
-// implementation to back up those ideas.
+// A `${` inside of a key means the previous one was never closed, so rather
+// than guessing, it's reported where the inner one starts
 mod mismatched {
     use super::*;
 
@@ -249,29 +262,57 @@ mod mismatched {
 
     #[test]
     fn ok() {
-        let mut args = HashMap::new();
-        args.insert("name", "Charles");
-        args.insert("smell", "human");
+        match Template::new(TEST) {
+            Err(e) => {
+                assert_eq!(e, Error::Nested(30));
+            },
+            Ok(_) => {
+                panic!("should have been an error");
+            }
+        }
+    }
 
-        let actual = Template::new(TEST).unwrap().render(&args).unwrap_err();
+    #[test]
+    fn display() {
+        let e = Errors {
+            inner: vec![Error::Nested(30), Error::Missing("name".into())],
+        };
+
+        assert_eq!(
+            format!("{}", e),
+            r#"nested delimiter opened at byte: 30; missing key: "name""#
+        );
+    }
+}
 
-        let mut errors = vec![
-            Error::Extra("smell".into()),
-            Error::Extra("name".into()),
-            Error::Missing("name. You smell like ${smell".into()),
-        ];
+// Braces that aren't part of a `${` are left alone, even when a `}` shows up
+// before the next `${`
+mod braces {
+    use super::*;
 
-        let expected_1 = Errors {
-            inner: errors.clone(),
+    static TEST: &str = "<style>p { color: red; }</style><p>${name}</p><script>if (x) { f(`${y}`) }</script>";
+
+    #[test]
+    fn ok() {
+        let args = hash_map! {
+            "name" => "Charles",
+            "y" => "z",
         };
 
-        // `Extra`s are swapped because hashmap iterators are random
-        errors.swap(0, 1);
-        let expected_2 = Errors {
-            inner: errors,
+        let s = Template::new(TEST).unwrap().render(&args).unwrap();
+
+        assert_eq!(s, "<style>p { color: red; }</style><p>Charles</p><script>if (x) { f(`z`) }</script>");
+    }
+
+    #[test]
+    fn dollar_in_key() {
+        let args = hash_map! {
+            "$price" => "5",
         };
 
-        assert!(actual == expected_1 || actual == expected_2);
+        let s = Template::new("costs ${$price}").unwrap().render(&args).unwrap();
+
+        assert_eq!(s, "costs 5");
     }
 }
 
@@ -286,3 +327,5 @@ mod mismatched {
 // and are willing to write the code for it, I'll happily merge it. However, I
 // personally don't need this, and so I'm probably not going to be bothered to
 // add this functionality myself.
+//
+// Update: `${raw}...${/raw}` now covers this, see `raw.rs`
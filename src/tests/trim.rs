@@ -0,0 +1,93 @@
+use crate::*;
+
+#[test]
+fn both_sides() {
+    let template = Template::new("<ul>\n    ${- item -}\n</ul>").unwrap();
+
+    assert_eq!(template.render_plain(), "<ul>${item}</ul>");
+    assert_eq!(template.keys(), &["item"]);
+
+    let args = hash_map! {
+        "item" => "<li>one</li>",
+    };
+
+    assert_eq!(template.render(&args).unwrap(), "<ul><li>one</li></ul>");
+}
+
+#[test]
+fn one_side() {
+    let before = Template::new("a   ${-b}   c").unwrap();
+    let after = Template::new("a   ${b-}   c").unwrap();
+
+    assert_eq!(before.render_plain(), "a${b}   c");
+    assert_eq!(after.render_plain(), "a   ${b}c");
+}
+
+#[test]
+fn no_spaces() {
+    let template = Template::new("a \n ${-b-} \n c").unwrap();
+
+    assert_eq!(template.render_plain(), "a${b}c");
+    assert_eq!(template.keys(), &["b"]);
+}
+
+#[test]
+fn between_placeholders() {
+    let template = Template::new("${a-}   \n   ${-b}").unwrap();
+
+    let args = hash_map! {
+        "a" => "1",
+        "b" => "2",
+    };
+
+    assert_eq!(template.render(&args).unwrap(), "12");
+}
+
+#[test]
+fn only_whitespace() {
+    let template = Template::builder("\n  ${- a -}  \n")
+        .whitespace(Whitespace::Preserve)
+        .build()
+        .unwrap();
+
+    assert_eq!(template.render_plain(), "${a}");
+}
+
+#[test]
+fn unmarked_keys_unchanged() {
+    let template = Template::new("${ spaced key }").unwrap();
+
+    assert_eq!(template.keys(), &[" spaced key "]);
+}
+
+#[test]
+fn blocks() {
+    let each = Template::new("<ul>\n  ${- #each items -}\n</ul>").unwrap();
+    let refs = Template::new("<input\n  ${- ref name -}\n>").unwrap();
+    let comment = Template::new("a\n  ${- ! note -}\n  b").unwrap();
+
+    assert_eq!(each.render_plain(), "<ul></ul>");
+    assert_eq!(each.keys(), &["items"]);
+    assert_eq!(refs.render_plain(), "<input>");
+    assert_eq!(comment.render_plain(), "ab");
+    assert!(comment.keys().is_empty());
+}
+
+#[test]
+fn raw_blocks() {
+    let outside = Template::new("a\n  ${- raw} ${x} ${/raw -}\n  b").unwrap();
+    let inside = Template::new("a ${raw -}\n  ${x}\n  ${- /raw} b").unwrap();
+    let spaced = Template::new("${- raw -}${x}${- /raw -}").unwrap();
+
+    assert_eq!(outside.render_plain(), "a ${x} b");
+    assert_eq!(inside.render_plain(), "a ${x} b");
+    assert_eq!(spaced.render_plain(), "${x}");
+    assert!(outside.keys().is_empty());
+}
+
+#[test]
+fn unmarked_comments_unchanged() {
+    let template = Template::new("a \n ${! note } \n b").unwrap();
+
+    assert_eq!(template.render_plain(), "a \n  \n b");
+}
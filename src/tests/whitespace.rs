@@ -0,0 +1,83 @@
+use crate::*;
+
+#[test]
+fn trim_is_default() {
+    let t = Template::builder("\n  <p>${name}</p>\n").build().unwrap();
+
+    assert_eq!(t.render_plain(), "<p>${name}</p>");
+}
+
+#[test]
+fn preserve() {
+    let t = Template::builder("\nDear ${name},\n\nThanks!\n")
+        .whitespace(Whitespace::Preserve)
+        .build()
+        .unwrap();
+
+    let args = hash_map! {
+        "name" => "Charles",
+    };
+
+    assert_eq!(t.render(&args).unwrap(), "\nDear Charles,\n\nThanks!\n");
+}
+
+#[test]
+fn collapse() {
+    let t = Template::builder("
+        <ul>
+            <li>${first}</li>
+            <li>${second}</li>
+        </ul>
+        <b>bold</b> <i>italic</i>
+    ")
+        .whitespace(Whitespace::Collapse)
+        .build()
+        .unwrap();
+
+    assert_eq!(
+        t.render_plain(),
+        "<ul><li>${first}</li><li>${second}</li></ul><b>bold</b> <i>italic</i>"
+    );
+
+    let args = hash_map! {
+        "first" => "1",
+        "second" => "2",
+    };
+
+    assert_eq!(
+        t.render(&args).unwrap(),
+        "<ul><li>1</li><li>2</li></ul><b>bold</b> <i>italic</i>"
+    );
+}
+
+#[test]
+fn collapse_keeps_text() {
+    let t = Template::builder("<p> hello </p>\n<p>\n${name}\n</p>")
+        .whitespace(Whitespace::Collapse)
+        .build()
+        .unwrap();
+
+    assert_eq!(t.render_plain(), "<p> hello </p><p>\n${name}\n</p>");
+}
+
+#[test]
+fn error_offsets_are_against_input() {
+    let input = "\n\n   Hello, ${name";
+
+    for whitespace in [Whitespace::Trim, Whitespace::Preserve, Whitespace::Collapse].iter() {
+        let e = Template::builder(input).whitespace(*whitespace).build().err().unwrap();
+
+        assert_eq!(e, Error::Unclosed(12));
+    }
+}
+
+#[test]
+fn borrowed_unless_collapsed() {
+    let input = "  <p>${name}</p>\n <p>x</p>  ";
+
+    let trimmed = Template::new(input).unwrap();
+    assert!(matches!(trimmed.template_str, std::borrow::Cow::Borrowed(_)));
+
+    let collapsed = Template::builder(input).whitespace(Whitespace::Collapse).build().unwrap();
+    assert!(matches!(collapsed.template_str, std::borrow::Cow::Owned(_)));
+}
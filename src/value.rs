@@ -0,0 +1,114 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt;
+
+/// Anything that can be used as a value in the map passed to
+/// [`Template::render`](crate::Template::render)
+///
+/// This covers every `AsRef<str>`, like `&str` and `String`, as well as
+/// [`Value`] for mixing plain strings with values that are computed on demand.
+pub trait ContextValue {
+    /// Called at most once per render, and only if the template uses the key
+    /// and there are no missing or extra keys
+    fn resolve(&self) -> Cow<'_, str>;
+
+    /// The items for a `${#each key}` list, if this is a list
+    ///
+    /// Only DOM renders use this, see [`Value::List`].
+    fn list(&self) -> Option<&[HashMap<&str, Value<'_>>]> {
+        None
+    }
+}
+
+impl<T: AsRef<str> + ?Sized> ContextValue for T {
+    fn resolve(&self) -> Cow<'_, str> {
+        Cow::Borrowed(self.as_ref())
+    }
+}
+
+/// A context value that is either a string or computed when it's needed
+///
+/// ```
+/// # use simple_html_template::{hash_map, RenderOptions, Template, Value};
+/// let template = Template::new("${name} has ${count} messages").unwrap();
+///
+/// let args = hash_map! {
+///     "name" => Value::from("Charles"),
+///     // Only called because the template uses it
+///     "count" => Value::lazy(|| 3.to_string()),
+///     // Never called
+///     "sidebar" => Value::lazy(|| unreachable!()),
+/// };
+///
+/// let s = template.render_with(&args, &RenderOptions::lenient()).unwrap();
+/// assert_eq!(s, "Charles has 3 messages");
+/// ```
+pub enum Value<'v> {
+    Str(Cow<'v, str>),
+    Lazy(Box<dyn Fn() -> String + 'v>),
+    /// The items for a `${#each key}` list, which DOM renders render a row
+    /// for each of. It's empty as text, so string renders leave lists out.
+    List(Vec<HashMap<&'v str, Value<'v>>>),
+}
+
+impl<'v> Value<'v> {
+    pub fn lazy(f: impl Fn() -> String + 'v) -> Self {
+        Self::Lazy(Box::new(f))
+    }
+}
+
+impl ContextValue for Value<'_> {
+    fn resolve(&self) -> Cow<'_, str> {
+        match self {
+            Self::Str(s) => Cow::Borrowed(s),
+            Self::Lazy(f) => Cow::Owned(f()),
+            Self::List(_) => Cow::Borrowed(""),
+        }
+    }
+
+    fn list(&self) -> Option<&[HashMap<&str, Value<'_>>]> {
+        match self {
+            Self::List(items) => Some(items),
+            _ => None,
+        }
+    }
+}
+
+// So that a map of `&Value`s can be rendered without moving the values out
+impl ContextValue for &Value<'_> {
+    fn resolve(&self) -> Cow<'_, str> {
+        (**self).resolve()
+    }
+
+    fn list(&self) -> Option<&[HashMap<&str, Value<'_>>]> {
+        (**self).list()
+    }
+}
+
+impl<'v> From<&'v str> for Value<'v> {
+    fn from(s: &'v str) -> Self {
+        Self::Str(Cow::Borrowed(s))
+    }
+}
+
+impl From<String> for Value<'_> {
+    fn from(s: String) -> Self {
+        Self::Str(Cow::Owned(s))
+    }
+}
+
+impl<'v> From<Vec<HashMap<&'v str, Value<'v>>>> for Value<'v> {
+    fn from(items: Vec<HashMap<&'v str, Value<'v>>>) -> Self {
+        Self::List(items)
+    }
+}
+
+impl fmt::Debug for Value<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Str(s) => f.debug_tuple("Str").field(s).finish(),
+            Self::Lazy(_) => write!(f, "Lazy(..)"),
+            Self::List(items) => f.debug_tuple("List").field(items).finish(),
+        }
+    }
+}
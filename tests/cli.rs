@@ -0,0 +1,106 @@
+// Runs the `sht` binary, so this only builds with the `cli` feature
+
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Output, Stdio};
+
+fn sht(args:&[&str], stdin:&str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_sht"))
+        .args(args)
+        .env("SHT_TEST_name", "from env")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(stdin.as_bytes()).unwrap();
+    child.wait_with_output().unwrap()
+}
+
+// A file in the temp dir, named after the test so they don't collide
+fn file(name:&str, contents:&str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("sht-{}-{}", std::process::id(), name));
+    fs::write(&path, contents).unwrap();
+    path
+}
+
+fn stdout(output:&Output) -> &str {
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    std::str::from_utf8(&output.stdout).unwrap()
+}
+
+#[test]
+fn json() {
+    let json = file("json.json", r#"{"name": "Charles", "count": 3, "site": {"title": "Home"}}"#);
+    let output = sht(&["-", "--json", json.to_str().unwrap()], "${site.title}: ${name} has ${count}");
+    assert_eq!(stdout(&output), "Home: Charles has 3");
+}
+
+#[test]
+fn toml() {
+    let toml = file("toml.toml", "name = \"Charles\"\nurgent = true\n[site]\ntitle = \"Home\"\n");
+    let output = sht(&["-", "--toml", toml.to_str().unwrap()], "${site.title}: ${name} ${urgent}");
+    assert_eq!(stdout(&output), "Home: Charles true");
+}
+
+#[test]
+fn env_and_set() {
+    let output = sht(&["-", "--env", "SHT_TEST_", "--set", "greeting=hi=there"], "${greeting} ${name}");
+    assert_eq!(stdout(&output), "hi=there from env");
+}
+
+#[test]
+fn later_sources_win() {
+    let json = file("later.json", r#"{"name": "json"}"#);
+    let output = sht(&["-", "-j", json.to_str().unwrap(), "-s", "name=set"], "${name}");
+    assert_eq!(stdout(&output), "set");
+
+    let output = sht(&["-", "-s", "name=set", "-j", json.to_str().unwrap()], "${name}");
+    assert_eq!(stdout(&output), "json");
+}
+
+#[test]
+fn template_file_and_output() {
+    let template = file("template.html", "<p>${name}</p>\n");
+    let out = std::env::temp_dir().join(format!("sht-{}-out.html", std::process::id()));
+    let output = sht(&[template.to_str().unwrap(), "-s", "name=x", "-o", out.to_str().unwrap(), "-w", "preserve"], "");
+    assert_eq!(stdout(&output), "");
+    assert_eq!(fs::read_to_string(&out).unwrap(), "<p>x</p>\n");
+}
+
+#[test]
+fn strict_and_lenient() {
+    let output = sht(&["-", "-s", "name=x", "-s", "extra=y"], "${name}");
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("extraneous key"));
+    assert!(output.stdout.is_empty());
+
+    let output = sht(&["-", "-s", "name=x", "-s", "extra=y", "--lenient"], "${name}");
+    assert_eq!(stdout(&output), "x");
+
+    // Lenient is only about extra keys
+    let output = sht(&["-", "--lenient"], "${name}");
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("missing key"));
+}
+
+#[test]
+fn bad_usage() {
+    assert_eq!(sht(&[], "").status.code(), Some(2));
+    assert_eq!(sht(&["-", "--bogus"], "").status.code(), Some(2));
+    assert_eq!(sht(&["-", "--set", "novalue"], "").status.code(), Some(2));
+    assert_eq!(sht(&["-", "--json"], "").status.code(), Some(2));
+}
+
+#[test]
+fn bad_input() {
+    let output = sht(&["-"], "${unclosed");
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("unclosed"));
+
+    let json = file("array.json", r#"{"list": [1, 2]}"#);
+    let output = sht(&["-", "-j", json.to_str().unwrap()], "${list}");
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("array"));
+}